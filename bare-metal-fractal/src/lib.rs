@@ -2,6 +2,7 @@
 #![no_std]
 
 extern crate alloc;
+extern crate fractal_core;
 extern crate wee_alloc;
 
 #[global_allocator]
@@ -10,12 +11,19 @@ static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 use alloc::alloc::alloc;
 use alloc::boxed::Box;
 use alloc::slice;
+use alloc::string::ToString;
+use alloc::vec::Vec;
 use core::alloc::Layout;
-use core::f32;
 use core::ffi::c_void;
 use core::intrinsics::abort;
 use core::mem;
-use core::ops::{Add, Deref, DerefMut, Index, IndexMut};
+use core::ops::{Deref, DerefMut, Index, IndexMut};
+use core::ptr;
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use fractal_core::{
+    build_palette, colour_iterations, default_palette, iterate_rect, shift_pixels, Colouring,
+    Formula, Frame, Progressive, Rect, Scene, TileCache, Viewport, INTERIOR, RGB, SCENE_WIDTH,
+};
 
 // Debugging
 #[cfg(debug_assertions)]
@@ -94,11 +102,22 @@ fn error_handler(_: core::alloc::Layout) -> ! {
 extern "C" fn eh_personality() {}
 
 // Memory Management
+// buf, and iters when enabled, hold at least w * h pixels
 #[repr(C)]
 pub struct TileBuffer {
     w: u32,
     h: u32,
     buf: BoxedSlice<RGB>,
+    palette: Vec<RGB>,
+    // Raw escape counts, see enable_iterations
+    iters: Option<BoxedSlice<u32>>,
+    // The view and max_iter the buffers hold a complete render of, if any,
+    // see pan_render
+    rendered: Option<(Viewport, u32)>,
+    // See render_tiles
+    tiles: Option<TileCache>,
+    // Text of the last save_scene
+    scene: Vec<u8>,
 }
 
 // Golfing away vec...
@@ -152,10 +171,64 @@ pub extern "C" fn alloc_tile(width: u32, height: u32) -> *mut c_void {
         w: width,
         h: height,
         buf: BoxedSlice::with_size((width * height) as usize),
+        palette: default_palette(),
+        iters: None,
+        rendered: None,
+        tiles: None,
+        scene: Vec::new(),
     };
     Box::into_raw(Box::new(tile)) as *mut c_void
 }
 
+// Reuses the tile's allocation when the new size fits in it, otherwise the
+// buffers move and get_buffer/get_iterations have to be called again.
+// Returns 1 when the allocation was reused. Also abandons any progressive
+// render, which has to be started again.
+#[no_mangle]
+pub extern "C" fn resize_tile(tile_ptr: *mut c_void, width: u32, height: u32) -> u32 {
+    let tile = unsafe { ref_tile(tile_ptr) };
+    let size = (width * height) as usize;
+    tile.w = width;
+    tile.h = height;
+    tile.rendered = None;
+    unsafe { PROGRESSIVE = None }
+    if size <= tile.buf.len() {
+        return 1;
+    }
+
+    tile.buf = BoxedSlice::with_size(size);
+    if tile.iters.is_some() {
+        tile.iters = Some(interior(size));
+    }
+    0
+}
+
+// Memory growth
+// Growing wasm memory detaches every view javascript holds on it, so the page
+// rebuilds its views whenever this has moved on from the generation it built
+// them in.
+static MEMORY_PAGES: AtomicUsize = AtomicUsize::new(0);
+static MEMORY_GENERATION: AtomicU32 = AtomicU32::new(0);
+
+#[cfg(target_arch = "wasm32")]
+fn memory_pages() -> usize {
+    core::arch::wasm32::memory_size(0)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn memory_pages() -> usize {
+    0
+}
+
+#[no_mangle]
+pub extern "C" fn memory_generation() -> u32 {
+    let pages = memory_pages();
+    if MEMORY_PAGES.swap(pages, Ordering::Relaxed) != pages {
+        MEMORY_GENERATION.fetch_add(1, Ordering::Relaxed);
+    }
+    MEMORY_GENERATION.load(Ordering::Relaxed)
+}
+
 unsafe fn ref_tile(tile_ptr: *mut c_void) -> &'static mut TileBuffer {
     &mut *(tile_ptr as *mut TileBuffer)
 }
@@ -177,174 +250,449 @@ pub extern "C" fn free_tile(tile_ptr: *mut c_void) {
     }
 }
 
-// Complex coordination
-// https://rustwasm.github.io/wasm-bindgen/examples/julia.html
-#[derive(Clone, Copy)]
-struct Complex {
-    re: f64,
-    im: f64,
+// Palettes
+// Javascript fills in the colour stops and the number of steps in the
+// gradient between each stop and the next, then binds it to tiles.
+#[repr(C)]
+pub struct PaletteBuffer {
+    stops: BoxedSlice<RGB>,
+    steps: BoxedSlice<u32>,
 }
 
-impl Complex {
-    fn square(self) -> Complex {
-        let re = (self.re * self.re) - (self.im * self.im);
-        let im = 2.0 * self.re * self.im;
-        Complex { re, im }
+// Returns null for fewer than two stops, which make no gradient
+#[no_mangle]
+pub extern "C" fn alloc_palette(stops: u32) -> *mut c_void {
+    if stops < 2 {
+        return ptr::null_mut();
     }
+    let palette = PaletteBuffer {
+        stops: BoxedSlice::with_size(stops as usize),
+        steps: BoxedSlice::with_size((stops - 1) as usize),
+    };
+    Box::into_raw(Box::new(palette)) as *mut c_void
+}
 
-    fn norm(&self) -> f64 {
-        (self.re * self.re) + (self.im * self.im)
+unsafe fn ref_palette(palette_ptr: *mut c_void) -> &'static mut PaletteBuffer {
+    &mut *(palette_ptr as *mut PaletteBuffer)
+}
+
+// rgba, 4 bytes per stop
+#[no_mangle]
+pub extern "C" fn get_palette_stops(palette_ptr: *mut c_void) -> *mut c_void {
+    let palette = unsafe { ref_palette(palette_ptr) };
+
+    palette.stops.as_mut_ptr() as *mut c_void
+}
+
+// u32 per gradient, one fewer than the stops
+#[no_mangle]
+pub extern "C" fn get_palette_steps(palette_ptr: *mut c_void) -> *mut c_void {
+    let palette = unsafe { ref_palette(palette_ptr) };
+
+    palette.steps.as_mut_ptr() as *mut c_void
+}
+
+#[no_mangle]
+pub extern "C" fn free_palette(palette_ptr: *mut c_void) {
+    if palette_ptr.is_null() {
+        return;
+    }
+    unsafe {
+        drop(Box::from_raw(palette_ptr as *mut PaletteBuffer));
     }
 }
 
-impl Add<Complex> for Complex {
-    type Output = Complex;
+// Builds the gradients into the tile's palette, the palette buffer can be
+// freed or refilled afterwards. Returns 0 and keeps the old palette when the
+// gradients have no steps, or for a null palette.
+#[no_mangle]
+pub extern "C" fn bind_palette(tile_ptr: *mut c_void, palette_ptr: *mut c_void) -> u32 {
+    if palette_ptr.is_null() {
+        return 0;
+    }
+    let tile = unsafe { ref_tile(tile_ptr) };
+    let palette = unsafe { ref_palette(palette_ptr) };
 
-    fn add(self, rhs: Complex) -> Complex {
-        Complex {
-            re: self.re + rhs.re,
-            im: self.im + rhs.im,
-        }
+    let mut colours = Vec::new();
+    for (stop, steps) in palette.stops.windows(2).zip(palette.steps.iter()) {
+        colours.extend(build_palette(&[[&stop[0], &stop[1]]], *steps as usize));
     }
+    if colours.is_empty() {
+        return 0;
+    }
+    tile.palette = colours;
+    tile.rendered = None;
+    1
 }
 
-// Mandelbrot maths
-fn mandel_iter(max_iter: u64, c: Complex) -> u64 {
-    let mut z: Complex = c;
+// Viewport state, so the page only has to forward input events
+static mut VIEWPORT: Viewport = Viewport::DEFAULT;
+
+fn viewport() -> Viewport {
+    unsafe { VIEWPORT }
+}
 
-    let mut iter = 1;
+fn set_viewport(viewport: Viewport) {
+    unsafe { VIEWPORT = viewport }
+}
+
+// Javascript jams
+#[no_mangle]
+pub extern "C" fn reset_view() {
+    set_viewport(Viewport::DEFAULT);
+}
+
+// Follow a drag of (dx, dy) pixels across the tile
+#[no_mangle]
+pub extern "C" fn pan(tile_ptr: *mut c_void, dx: f64, dy: f64) {
+    let tile = unsafe { ref_tile(tile_ptr) };
+    let mut view = viewport();
+    view.pan(tile.w, dx, dy);
+    set_viewport(view);
+}
 
-    while (z.norm() <= 4.0) && (iter < max_iter) {
-        z = c + z.square();
-        iter = iter + 1;
+// Follow a drag like pan, but when it's by whole pixels and the tile holds a
+// complete render of the view being left, shift that render along and only
+// compute the strips it uncovers. Returns 1 when it did, otherwise 0 and the
+// caller renders the new view in full.
+#[no_mangle]
+pub extern "C" fn pan_render(tile_ptr: *mut c_void, dx: f64, dy: f64, max_iter: u32) -> u32 {
+    let tile = unsafe { ref_tile(tile_ptr) };
+    let before = viewport();
+    pan(tile_ptr, dx, dy);
+
+    let whole = dx as i32 as f64 == dx && dy as i32 as f64 == dy;
+    if !whole || tile.rendered != Some((before, max_iter)) {
+        tile.rendered = None;
+        return 0;
     }
 
-    if iter == max_iter {
-        0
-    } else {
-        iter
+    let (width, height) = (tile.w, tile.h);
+    let size = tile.size();
+    let strips = shift_pixels(&mut tile.buf.0[..size], width, height, dx as i32, dy as i32);
+    if let Some(iters) = &mut tile.iters {
+        shift_pixels(&mut iters.0[..size], width, height, dx as i32, dy as i32);
+    }
+    let view = viewport();
+    let frame = view.frame(width, height);
+    for rect in strips.iter() {
+        let offset = (rect.y * width + rect.x) as usize;
+        tile.render(offset, width as usize, &frame, *rect, max_iter as u64);
     }
+    tile.rendered = Some((view, max_iter));
+    1
 }
 
-// With this byte order javascript can copy it straight into canvas
-#[repr(C)]
-#[derive(Clone, Copy)]
-struct RGB {
-    r: u8,
-    g: u8,
-    b: u8,
-    a: u8,
+// Zoom in by factor, or out when below 1, around pixel (x, y) of the tile
+#[no_mangle]
+pub extern "C" fn zoom(tile_ptr: *mut c_void, x: f64, y: f64, factor: f64) {
+    let tile = unsafe { ref_tile(tile_ptr) };
+    let mut view = viewport();
+    view.zoom_at(tile.w, tile.h, x, y, factor);
+    set_viewport(view);
+}
+
+#[no_mangle]
+pub extern "C" fn get_view_width() -> f64 {
+    viewport().width
+}
+
+// Scene files
+// Writes the current view out as a fractal_core::Scene so fractal-rs can
+// render it again, returning the length of the text at get_scene. zoom is
+// log2(get_scene_width() / get_view_width()), there's no log without std.
+#[no_mangle]
+pub extern "C" fn get_scene_width() -> f64 {
+    SCENE_WIDTH
 }
 
-fn tween_one(progress: i32, from: u8, to: u8) -> u8 {
-    let from = from as i32;
-    let to = to as i32;
-    (from + (to - from) * progress / 255) as u8
+#[no_mangle]
+pub extern "C" fn save_scene(tile_ptr: *mut c_void, max_iter: u32, zoom: f64) -> u32 {
+    let tile = unsafe { ref_tile(tile_ptr) };
+    let view = viewport();
+    let scene = Scene {
+        formula: Formula::Mandelbrot,
+        center_re: view.center_re.to_string(),
+        center_im: view.center_im.to_string(),
+        zoom: zoom.to_string(),
+        max_iter: max_iter as u64,
+        width: tile.w,
+        height: tile.h,
+        supersample: 1,
+        colouring: Colouring::Bands,
+        palette: tile.palette.clone(),
+    };
+    tile.scene = scene.to_string().into_bytes();
+    tile.scene.len() as u32
 }
 
-impl RGB {
-    fn rgb(r: u8, g: u8, b: u8) -> Self {
-        Self { r, g, b, a: 255 }
+#[no_mangle]
+pub extern "C" fn get_scene(tile_ptr: *mut c_void) -> *const u8 {
+    let tile = unsafe { ref_tile(tile_ptr) };
+    tile.scene.as_ptr()
+}
+
+// Render the current view
+#[no_mangle]
+pub extern "C" fn render_view(tile_ptr: *mut c_void, max_iter: u32) {
+    let tile = unsafe { ref_tile(tile_ptr) };
+    render_frame_safe(tile, max_iter, viewport())
+}
+
+// Iteration buffers
+// Once enabled, every render also writes the raw escape count of each pixel,
+// 0 for interior points, so the page can recolour or inspect them without
+// iterating again.
+#[no_mangle]
+pub extern "C" fn enable_iterations(tile_ptr: *mut c_void) {
+    let tile = unsafe { ref_tile(tile_ptr) };
+    if tile.iters.is_none() {
+        tile.iters = Some(interior(tile.buf.len()));
+        tile.rendered = None;
     }
+}
 
-    fn tween(&self, progress: i32, to: &RGB) -> RGB {
-        RGB::rgb(
-            tween_one(progress, self.r, to.r),
-            tween_one(progress, self.g, to.g),
-            tween_one(progress, self.b, to.b),
-            // This invalid code saves 2k?
-            // (to.r as u16 * 255 / progress) as u8,
-        )
+fn interior(size: usize) -> BoxedSlice<u32> {
+    let mut iters = BoxedSlice::with_size(size);
+    for iter in iters.iter_mut() {
+        *iter = INTERIOR;
     }
+    iters
 }
 
-static BOTTOM: RGB = RGB {
-    r: 0,
-    g: 0,
-    b: 0,
-    a: 255,
-};
+// u32 per pixel, null until enable_iterations
+#[no_mangle]
+pub extern "C" fn get_iterations(tile_ptr: *mut c_void) -> *mut c_void {
+    let tile = unsafe { ref_tile(tile_ptr) };
+
+    match &mut tile.iters {
+        Some(iters) => iters.as_mut_ptr() as *mut c_void,
+        None => ptr::null_mut(),
+    }
+}
 
-// golfing to do here...
-fn build_palette(gradients: &BoxedSlice<[&RGB; 2]>, steps_per_grad: usize) -> BoxedSlice<RGB> {
-    let num_gradients = gradients.len();
-    let mut palette = BoxedSlice::with_size(num_gradients * steps_per_grad);
-    for i in 0..num_gradients {
-        let color = gradients[i][0];
-        let next_color = gradients[i][1];
-        for step in 0..steps_per_grad {
-            let progress = (step * 255 / steps_per_grad) as i32;
-
-            palette[i * steps_per_grad + step] = color.tween(progress, next_color);
+// Colour the last render again with the tile's current palette
+#[no_mangle]
+pub extern "C" fn recolour(tile_ptr: *mut c_void) {
+    let tile = unsafe { ref_tile(tile_ptr) };
+    let size = tile.size();
+    if let Some(iters) = &tile.iters {
+        colour_iterations(&iters.0[..size], &mut tile.buf.0[..size], &tile.palette);
+    }
+}
+
+// Colour cycling, called once per animation frame. Shifts the palette offset
+// by step entries and recolours from the iteration buffer, so no iterations
+// are recomputed. Later renders keep the offset.
+#[no_mangle]
+pub extern "C" fn cycle(tile_ptr: *mut c_void, step: u32) {
+    let tile = unsafe { ref_tile(tile_ptr) };
+    let len = tile.palette.len();
+    tile.palette.rotate_left(step as usize % len);
+    if tile.iters.is_none() {
+        tile.rendered = None;
+    }
+    recolour(tile_ptr);
+}
+
+impl TileBuffer {
+    // Pixels in use, the buffers can be bigger after resize_tile
+    fn size(&self) -> usize {
+        (self.w * self.h) as usize
+    }
+
+    fn render(&mut self, offset: usize, stride: usize, frame: &Frame, rect: Rect, max_iter: u64) {
+        match &mut self.iters {
+            Some(iters) => {
+                iterate_rect(iters, offset, stride, frame, rect, max_iter);
+                for j in 0..rect.height as usize {
+                    let row = offset + j * stride..offset + j * stride + rect.width as usize;
+                    colour_iterations(&iters.0[row.clone()], &mut self.buf.0[row], &self.palette);
+                }
+            }
+            None => fractal_core::render_rect(
+                &mut self.buf,
+                offset,
+                stride,
+                frame,
+                rect,
+                max_iter,
+                &self.palette,
+            ),
         }
     }
-    palette
 }
 
-fn mandel_color(i: u64, palette: &BoxedSlice<RGB>) -> RGB {
-    if i == 0 {
-        BOTTOM
-    } else {
-        // This is on the hot loop, can len be removed?
-        palette[(i % palette.len() as u64) as usize]
+// In progress render, with the view and max_iter it was started for, see
+// start_render
+static mut PROGRESSIVE: Option<(Progressive, Viewport, u32)> = None;
+
+// Begin rendering the current view a step at a time, coarse to fine unless
+// coarse is 0
+#[no_mangle]
+pub extern "C" fn start_render(tile_ptr: *mut c_void, max_iter: u32, coarse: u32) {
+    let tile = unsafe { ref_tile(tile_ptr) };
+    let view = viewport();
+    let render = Progressive::new(
+        view.frame(tile.w, tile.h),
+        tile.w,
+        tile.h,
+        max_iter as u64,
+        coarse != 0,
+    );
+    tile.rendered = None;
+    unsafe { PROGRESSIVE = Some((render, view, max_iter)) }
+}
+
+// Sample at most budget more pixels into the tile started with start_render,
+// returning the fraction done so far, 1 when there's no render to continue
+#[no_mangle]
+pub extern "C" fn step(tile_ptr: *mut c_void, budget: u32) -> f64 {
+    let tile = unsafe { ref_tile(tile_ptr) };
+    let (mut render, view, max_iter) = match unsafe { PROGRESSIVE } {
+        Some(progressive) => progressive,
+        None => return 1.0,
+    };
+    match &mut tile.iters {
+        Some(iters) => render.step_with_iterations(&mut tile.buf, iters, &tile.palette, budget),
+        None => render.step(&mut tile.buf, &tile.palette, budget),
+    };
+    if render.finished() {
+        tile.rendered = Some((view, max_iter));
+    }
+    unsafe { PROGRESSIVE = Some((render, view, max_iter)) }
+    render.progress()
+}
+
+// Tiled rendering
+// Frames composited from a cache of fixed size tiles, so going back to
+// somewhere already seen is instant. Tiles are resampled rather than lined up
+// with pixels.
+const TILE_SIZE: u32 = 128;
+const TILE_CAPACITY: usize = 128;
+
+// Make at most budget of the current view's missing tiles and composite it
+// into the tile, returning how many are still missing
+#[no_mangle]
+pub extern "C" fn render_tiles(tile_ptr: *mut c_void, max_iter: u32, budget: u32) -> u32 {
+    let tile = unsafe { ref_tile(tile_ptr) };
+    let (width, height) = (tile.w, tile.h);
+    let size = tile.size();
+    let frame = viewport().frame(width, height);
+    tile.rendered = None;
+    let cache = tile
+        .tiles
+        .get_or_insert_with(|| TileCache::new(TILE_SIZE, max_iter as u64, TILE_CAPACITY));
+    cache.set_max_iter(max_iter as u64);
+
+    let missing = match &mut tile.iters {
+        Some(iters) => cache.render_with_iterations(
+            &mut tile.buf.0[..size],
+            &mut iters.0[..size],
+            width,
+            height,
+            &frame,
+            &tile.palette,
+            budget as usize,
+        ),
+        None => cache.render(
+            &mut tile.buf.0[..size],
+            width,
+            height,
+            &frame,
+            &tile.palette,
+            budget as usize,
+        ),
+    };
+    missing as u32
+}
+
+// Drop every tile cached for the tile
+#[no_mangle]
+pub extern "C" fn clear_tiles(tile_ptr: *mut c_void) {
+    let tile = unsafe { ref_tile(tile_ptr) };
+    tile.tiles = None;
+}
+
+// Render the (x, y, width, height) part of a frame_width x frame_height frame
+// of the given view, for splitting a frame across workers. Pixel (x + i, y + j)
+// goes to pixel offset + j * stride + i of the tile. Returns 0 without
+// rendering when the part doesn't fit in the tile.
+#[no_mangle]
+pub extern "C" fn render_rect(
+    tile_ptr: *mut c_void,
+    offset: u32,
+    stride: u32,
+    max_iter: u32,
+    center_re: f64,
+    center_im: f64,
+    viewport_width: f64,
+    frame_width: u32,
+    frame_height: u32,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+) -> u32 {
+    let tile = unsafe { ref_tile(tile_ptr) };
+    let rect = Rect {
+        x,
+        y,
+        width,
+        height,
+    };
+    if !rect.fits(tile.size(), offset as usize, stride as usize) {
+        return 0;
+    }
+    tile.rendered = None;
+
+    let frame = Viewport {
+        center_re,
+        center_im,
+        width: viewport_width,
     }
+    .frame(frame_width, frame_height);
+    tile.render(
+        offset as usize,
+        stride as usize,
+        &frame,
+        rect,
+        max_iter as u64,
+    );
+    1
 }
 
-// Javascript jams
 #[no_mangle]
 pub extern "C" fn render(
     tile_ptr: *mut c_void,
     max_iter: u32,
-    center_re: f32,
-    center_im: f32,
-    viewport_width: f32,
+    center_re: f64,
+    center_im: f64,
+    viewport_width: f64,
 ) {
     let tile = unsafe { ref_tile(tile_ptr) };
-    render_frame_safe(tile, max_iter, center_re, center_im, viewport_width)
+    let view = Viewport {
+        center_re,
+        center_im,
+        width: viewport_width,
+    };
+    render_frame_safe(tile, max_iter, view)
 }
 
 // We split this out so that we can escape 'unsafe' as quickly
 // as possible.
-fn render_frame_safe(
-    tile: &mut TileBuffer,
-    max_iter: u32,
-    center_re: f32,
-    center_im: f32,
-    viewport_width: f32,
-) {
+fn render_frame_safe(tile: &mut TileBuffer, max_iter: u32, view: Viewport) {
     let width = tile.w;
     let height = tile.h;
 
-    let step = (viewport_width / width as f32) as f64;
-    let start_re = (center_re - viewport_width / 2.0) as f64;
-    let start_im = (center_im - (viewport_width * (height as f32 / width as f32)) / 2.0) as f64;
-    let blue = RGB::rgb(0, 183, 255);
-    let orange = RGB::rgb(255, 128, 0);
-    let black = RGB::rgb(0, 0, 0);
-    let white = RGB::rgb(255, 255, 255);
-
-    let gradients = BoxedSlice(Box::from([
-        [&black, &blue],
-        [&blue, &white],
-        [&white, &orange],
-        [&orange, &black],
-    ]));
-
-    let palette = build_palette(&gradients, 4);
-
-    for y in 0..height {
-        for x in 0..width {
-            let c = mandel_color(
-                mandel_iter(
-                    max_iter as u64,
-                    Complex {
-                        re: start_re + ((x as f64) * step),
-                        im: start_im + ((y as f64) * step),
-                    },
-                ),
-                &palette,
-            );
-            tile.buf[(y * width + x) as usize] = c;
-        }
-    }
+    let frame = view.frame(width, height);
+
+    let rect = Rect {
+        x: 0,
+        y: 0,
+        width,
+        height,
+    };
+    tile.render(0, width as usize, &frame, rect, max_iter as u64);
+    tile.rendered = Some((view, max_iter));
 }
@@ -0,0 +1,45 @@
+// Renders parts of a frame for workers.html
+//
+// Every worker has its own instance, and so its own memory, of the module.
+// Parts are rendered into a tile here and then copied into the page's
+// SharedArrayBuffer, or sent back when the page isn't cross origin isolated.
+
+const ready = WebAssembly.instantiateStreaming(
+  fetch("./bare_metal_fractal.wasm"),
+  {
+    "env": {
+      js_log_msg: () => console.log("wasm debug message")
+    },
+  }
+).then(({ instance }) => instance.exports);
+
+onmessage = async ({ data }) => {
+  const { alloc_tile, free_tile, get_buffer, render_rect, memory } = await ready;
+  const { id, view, frameWidth, frameHeight, rect, maxIter, shared } = data;
+  const { x, y, width, height } = rect;
+
+  const tile = alloc_tile(width, height);
+  render_rect(
+    tile, 0, width, maxIter,
+    view.re, view.im, view.width,
+    frameWidth, frameHeight,
+    x, y, width, height
+  );
+  const pixels = new Uint8ClampedArray(memory.buffer, get_buffer(tile), 4 * width * height);
+
+  if (shared) {
+    const frame = new Uint8ClampedArray(shared);
+    for (let row = 0; row < height; row++) {
+      frame.set(
+        pixels.subarray(4 * row * width, 4 * (row + 1) * width),
+        4 * ((y + row) * frameWidth + x)
+      );
+    }
+    free_tile(tile);
+    postMessage({ id, rect });
+  } else {
+    const copy = pixels.slice();
+    free_tile(tile);
+    postMessage({ id, rect, pixels: copy }, [copy.buffer]);
+  }
+};
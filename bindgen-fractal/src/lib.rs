@@ -1,14 +1,23 @@
+extern crate fractal_core;
 extern crate wasm_bindgen;
 extern crate wee_alloc;
 
-use std::ops::Add;
 use std::slice;
 
+use fractal_core::{
+    colour_iterations, default_palette, iterate_rect, shift_pixels, Colouring, Frame, Progressive,
+    Rect, Scene, TileCache, Viewport, BOTTOM, INTERIOR, RGB, SCENE_WIDTH,
+};
+
 use web_sys::{CanvasRenderingContext2d, ImageData};
 
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::Clamped;
 
+// Tiled rendering, see Renderer::render_tiles
+const TILE_SIZE: u32 = 128;
+const TILE_CAPACITY: usize = 128;
+
 #[global_allocator]
 static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
@@ -42,14 +51,29 @@ pub struct TileBuffer {
     w: usize,
     h: usize,
     buf: Vec<RGB>,
+    // Raw escape counts, see Renderer::keep_iterations
+    iters: Option<Vec<u32>>,
 }
 
 impl TileBuffer {
     fn with_size(width: usize, height: usize) -> Self {
-        TileBuffer {
-            w: width,
-            h: height,
-            buf: Vec::with_capacity((width * height) as usize),
+        let mut tile = TileBuffer {
+            w: 0,
+            h: 0,
+            buf: Vec::new(),
+            iters: None,
+        };
+        tile.resize(width, height);
+        tile
+    }
+
+    // Keeps the allocation when shrinking, so resizing back is free
+    fn resize(&mut self, width: usize, height: usize) {
+        self.w = width;
+        self.h = height;
+        self.buf.resize(width * height, BOTTOM);
+        if let Some(iters) = &mut self.iters {
+            iters.resize(width * height, INTERIOR);
         }
     }
 
@@ -63,180 +87,389 @@ impl TileBuffer {
     }
 }
 
-// Complex coordination
-// https://rustwasm.github.io/wasm-bindgen/examples/julia.html
-#[derive(Clone, Copy)]
-struct Complex {
-    re: f64,
-    im: f64,
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Formula {
+    Mandelbrot,
+}
+
+// Everything needed to draw frames, kept between draws so that only the
+// pixels are recomputed
+#[wasm_bindgen]
+pub struct Renderer {
+    tile: TileBuffer,
+    palette: Vec<RGB>,
+    formula: Formula,
+    view: Viewport,
+    max_iter: u32,
+    // With the view and max_iter it was started for
+    progressive: Option<(Progressive, Viewport, u32)>,
+    // The view and max_iter the tile holds a complete render of, if any,
+    // with the current palette and formula, see pan_render
+    rendered: Option<(Viewport, u32)>,
+    tiles: Option<TileCache>,
 }
 
-impl Complex {
-    fn square(self) -> Complex {
-        let re = (self.re * self.re) - (self.im * self.im);
-        let im = 2.0 * self.re * self.im;
-        Complex { re, im }
+#[wasm_bindgen]
+impl Renderer {
+    #[wasm_bindgen(constructor)]
+    pub fn new(width: usize, height: usize) -> Renderer {
+        Renderer {
+            tile: TileBuffer::with_size(width, height),
+            palette: default_palette(),
+            formula: Formula::Mandelbrot,
+            view: Viewport::DEFAULT,
+            max_iter: 100,
+            progressive: None,
+            rendered: None,
+            tiles: None,
+        }
+    }
+
+    // Also abandons any progressive render, which has to be started again
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.tile.resize(width, height);
+        self.progressive = None;
+        self.rendered = None;
     }
 
-    fn norm(&self) -> f64 {
-        (self.re * self.re) + (self.im * self.im)
+    pub fn set_view(&mut self, center_re: f64, center_im: f64, viewport_width: f64) {
+        self.view = Viewport {
+            center_re,
+            center_im,
+            width: viewport_width,
+        };
     }
-}
 
-impl Add<Complex> for Complex {
-    type Output = Complex;
+    // Follow a drag of (dx, dy) pixels
+    pub fn pan(&mut self, dx: f64, dy: f64) {
+        self.view.pan(self.tile.w as u32, dx, dy);
+    }
+
+    // Follow a drag like pan, but when it's by whole pixels and the tile holds
+    // a complete render of the view being left, shift that render along and
+    // only compute the strips it uncovers. Returns whether it did, otherwise
+    // draw or start a render of the new view. Call present to show it.
+    pub fn pan_render(&mut self, dx: f64, dy: f64) -> bool {
+        let before = self.view;
+        self.pan(dx, dy);
+
+        let whole = dx as i32 as f64 == dx && dy as i32 as f64 == dy;
+        if !whole || self.rendered != Some((before, self.max_iter)) {
+            self.rendered = None;
+            return false;
+        }
 
-    fn add(self, rhs: Complex) -> Complex {
-        Complex {
-            re: self.re + rhs.re,
-            im: self.im + rhs.im,
+        let (width, height) = (self.tile.w as u32, self.tile.h as u32);
+        let strips = shift_pixels(&mut self.tile.buf, width, height, dx as i32, dy as i32);
+        if let Some(iters) = &mut self.tile.iters {
+            shift_pixels(iters, width, height, dx as i32, dy as i32);
+        }
+        let frame = self.view.frame(width, height);
+        for rect in strips.iter() {
+            self.render_part(&frame, *rect);
         }
+        self.rendered = Some((self.view, self.max_iter));
+        true
     }
-}
 
-// Mandelbrot maths
-fn mandel_iter(max_iter: u64, c: Complex) -> u64 {
-    let mut z: Complex = c;
+    // Zoom in by factor, or out when below 1, around pixel (x, y)
+    pub fn zoom_at(&mut self, x: f64, y: f64, factor: f64) {
+        self.view
+            .zoom_at(self.tile.w as u32, self.tile.h as u32, x, y, factor);
+    }
 
-    let mut iter = 1;
+    // Colours as rgba bytes, four per palette entry
+    pub fn set_palette(&mut self, rgba: &[u8]) -> Result<(), JsValue> {
+        if rgba.is_empty() || !rgba.chunks_exact(4).remainder().is_empty() {
+            return Err(JsValue::from_str(
+                "palette must be a non empty list of rgba bytes",
+            ));
+        }
+        self.palette = rgba
+            .chunks(4)
+            .map(|c| RGB {
+                r: c[0],
+                g: c[1],
+                b: c[2],
+                a: c[3],
+            })
+            .collect();
+        self.rendered = None;
+        Ok(())
+    }
 
-    while (z.norm() <= 4.0) && (iter < max_iter) {
-        z = c + z.square();
-        iter = iter + 1;
+    pub fn reset_palette(&mut self) {
+        self.palette = default_palette();
+        self.rendered = None;
     }
 
-    if iter == max_iter {
-        0
-    } else {
-        iter
+    pub fn set_formula(&mut self, formula: Formula) {
+        self.formula = formula;
+        self.rendered = None;
     }
-}
 
-// With this byte order javascript can copy it straight into canvas
-#[derive(Clone, Copy)]
-struct RGB {
-    r: u8,
-    g: u8,
-    b: u8,
-    _a: u8,
-}
+    pub fn set_max_iter(&mut self, max_iter: u32) {
+        self.max_iter = max_iter;
+    }
 
-fn tween_one(progress: i32, from: u8, to: u8) -> u8 {
-    let from = from as i32;
-    let to = to as i32;
-    (from + (to - from) * progress / 255) as u8
-}
+    pub fn draw(&mut self, ctx: &CanvasRenderingContext2d) -> Result<(), JsValue> {
+        dbg!("Rendering a {}x{} fractal", self.tile.w, self.tile.h);
+        self.render_tile();
+        self.present(ctx)
+    }
 
-impl RGB {
-    fn rgb(r: u8, g: u8, b: u8) -> Self {
-        Self { r, g, b, _a: 255 }
+    // Begin rendering the current settings a step at a time, see step
+    pub fn start(&mut self, coarse: bool) {
+        let (width, height) = (self.tile.w as u32, self.tile.h as u32);
+        let frame = self.view.frame(width, height);
+        let render = Progressive::new(frame, width, height, self.max_iter as u64, coarse);
+        self.progressive = Some((render, self.view, self.max_iter));
+        self.rendered = None;
     }
 
-    fn tween(&self, progress: i32, to: &RGB) -> RGB {
-        RGB::rgb(
-            tween_one(progress, self.r, to.r),
-            tween_one(progress, self.g, to.g),
-            tween_one(progress, self.b, to.b),
-            // This invalid code saves 2k?
-            // (to.r as u16 * 255 / progress) as u8,
-        )
+    // Sample at most budget more pixels, returning the fraction done so far.
+    // Call present to show them.
+    pub fn step(&mut self, budget: u32) -> f64 {
+        match &mut self.progressive {
+            Some((render, view, max_iter)) => {
+                match &mut self.tile.iters {
+                    Some(iters) => render.step_with_iterations(
+                        &mut self.tile.buf,
+                        iters,
+                        &self.palette,
+                        budget,
+                    ),
+                    None => render.step(&mut self.tile.buf, &self.palette, budget),
+                };
+                if render.finished() {
+                    self.rendered = Some((*view, *max_iter));
+                }
+                render.progress()
+            }
+            None => 1.0,
+        }
     }
-}
 
-static BOTTOM: RGB = RGB {
-    r: 0,
-    g: 0,
-    b: 0,
-    _a: 255,
-};
+    // Composite the current view from a cache of fixed size tiles, making at
+    // most budget of the missing ones first, so going back to somewhere
+    // already seen is instant. Returns how many are still missing, call
+    // present to show it. Tiles are resampled rather than lined up with
+    // pixels.
+    pub fn render_tiles(&mut self, budget: usize) -> usize {
+        let max_iter = self.max_iter as u64;
+        let cache = self
+            .tiles
+            .get_or_insert_with(|| TileCache::new(TILE_SIZE, max_iter, TILE_CAPACITY));
+        cache.set_max_iter(max_iter);
+
+        let (width, height) = (self.tile.w as u32, self.tile.h as u32);
+        let frame = self.view.frame(width, height);
+        self.rendered = None;
+        match (self.formula, &mut self.tile.iters) {
+            (Formula::Mandelbrot, Some(iters)) => cache.render_with_iterations(
+                &mut self.tile.buf,
+                iters,
+                width,
+                height,
+                &frame,
+                &self.palette,
+                budget,
+            ),
+            (Formula::Mandelbrot, None) => cache.render(
+                &mut self.tile.buf,
+                width,
+                height,
+                &frame,
+                &self.palette,
+                budget,
+            ),
+        }
+    }
+
+    // Drop every cached tile
+    pub fn clear_tiles(&mut self) {
+        self.tiles = None;
+    }
+
+    // Render the (x, y, width, height) part of a frame_width x frame_height
+    // frame into rgba bytes, for splitting a frame across workers. Pixel
+    // (x + i, y + j) goes to pixel offset + j * stride + i of buf. The
+    // renderer's own size is unused.
+    //
+    // wasm-bindgen copies all of buf in and back out, so workers sharing one
+    // frame buffer each pass only their band, e.g. a subarray of a
+    // SharedArrayBuffer view starting at the band's first row with offset 0.
+    // Passing the whole view would write every other band back stale.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render_rect(
+        &self,
+        buf: &mut [u8],
+        offset: usize,
+        stride: usize,
+        frame_width: u32,
+        frame_height: u32,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), JsValue> {
+        let rect = Rect {
+            x,
+            y,
+            width,
+            height,
+        };
+        if !rect.fits(buf.len() / 4, offset, stride) {
+            return Err(JsValue::from_str("rect doesn't fit in the buffer"));
+        }
+        // RGB is 4 bytes with an alignment of 1, just like the canvas data
+        let pixels =
+            unsafe { slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut RGB, buf.len() / 4) };
+        let frame = self.view.frame(frame_width, frame_height);
+        match self.formula {
+            Formula::Mandelbrot => fractal_core::render_rect(
+                pixels,
+                offset,
+                stride,
+                &frame,
+                rect,
+                self.max_iter as u64,
+                &self.palette,
+            ),
+        }
+        Ok(())
+    }
+
+    // While kept, every draw and step also records the raw escape count of
+    // each pixel, 0 for interior points, for recolour and iterations
+    pub fn keep_iterations(&mut self, keep: bool) {
+        self.tile.iters = if keep {
+            Some(vec![INTERIOR; self.tile.w * self.tile.h])
+        } else {
+            None
+        };
+        self.rendered = None;
+    }
+
+    // A copy of the raw escape counts, row major
+    pub fn iterations(&self) -> Option<Vec<u32>> {
+        self.tile.iters.clone()
+    }
+
+    // The current view as a scene file, so fractal-rs can render it again
+    // (larger, supersampled or smooth). Scene palettes are opaque, so a
+    // translucent one from set_palette is refused rather than flattened.
+    pub fn scene(&self) -> Result<String, JsValue> {
+        if self.palette.iter().any(|c| c.a != 255) {
+            return Err(JsValue::from_str("scene palettes can't be translucent"));
+        }
+        let formula = match self.formula {
+            Formula::Mandelbrot => fractal_core::Formula::Mandelbrot,
+        };
+        let scene = Scene {
+            formula,
+            center_re: self.view.center_re.to_string(),
+            center_im: self.view.center_im.to_string(),
+            zoom: (SCENE_WIDTH / self.view.width).log2().to_string(),
+            max_iter: self.max_iter as u64,
+            width: self.tile.w as u32,
+            height: self.tile.h as u32,
+            supersample: 1,
+            colouring: Colouring::Bands,
+            palette: self.palette.clone(),
+        };
+        Ok(scene.to_string())
+    }
+
+    // Colour the last render again with the current palette, without
+    // iterating. Call present to show it.
+    pub fn recolour(&mut self) {
+        if let Some(iters) = &self.tile.iters {
+            colour_iterations(iters, &mut self.tile.buf, &self.palette);
+        }
+    }
 
-// golfing to do here...
-fn build_palette(gradients: &Vec<[&RGB; 2]>, steps_per_grad: usize) -> Vec<RGB> {
-    let num_gradients = gradients.len();
-    let mut palette = Vec::with_capacity(num_gradients * steps_per_grad);
-    for i in 0..num_gradients {
-        let color = gradients[i][0];
-        let next_color = gradients[i][1];
-        for step in 0..steps_per_grad {
-            let progress = (step * 255 / steps_per_grad) as i32;
-            palette.push(color.tween(progress, next_color));
+    // Colour cycling, call once per animation frame. Shifts the palette
+    // offset by step entries and recolours from the kept iterations, so no
+    // iterations are recomputed. Later draws keep the offset.
+    pub fn cycle(&mut self, step: u32) {
+        let len = self.palette.len();
+        self.palette.rotate_left(step as usize % len);
+        if self.tile.iters.is_none() {
+            self.rendered = None;
         }
+        self.recolour();
+    }
+
+    // Puts the buffer as it is on the canvas
+    pub fn present(&mut self, ctx: &CanvasRenderingContext2d) -> Result<(), JsValue> {
+        let (width, height) = (self.tile.w as u32, self.tile.h as u32);
+        let data = ImageData::new_with_u8_clamped_array_and_sh(
+            Clamped(self.tile.get_mut_buf()),
+            width,
+            height,
+        )?;
+        ctx.put_image_data(&data, 0.0, 0.0)
     }
-    palette
 }
 
-fn mandel_color(i: u64, palette: &Vec<RGB>) -> RGB {
-    if i == 0 {
-        BOTTOM
-    } else {
-        // This is on the hot loop, can len be removed?
-        palette[(i % palette.len() as u64) as usize]
+impl Renderer {
+    fn render_tile(&mut self) {
+        let width = self.tile.w as u32;
+        let height = self.tile.h as u32;
+        let frame = self.view.frame(width, height);
+        let rect = Rect {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        };
+        self.render_part(&frame, rect);
+        self.rendered = Some((self.view, self.max_iter));
+    }
+
+    // Part of a frame the size of the tile, in place
+    fn render_part(&mut self, frame: &Frame, rect: Rect) {
+        let stride = self.tile.w;
+        let offset = rect.y as usize * stride + rect.x as usize;
+        let max_iter = self.max_iter as u64;
+
+        match (self.formula, &mut self.tile.iters) {
+            (Formula::Mandelbrot, Some(iters)) => {
+                iterate_rect(iters, offset, stride, frame, rect, max_iter);
+                for j in 0..rect.height as usize {
+                    let row = offset + j * stride..offset + j * stride + rect.width as usize;
+                    colour_iterations(&iters[row.clone()], &mut self.tile.buf[row], &self.palette);
+                }
+            }
+            (Formula::Mandelbrot, None) => fractal_core::render_rect(
+                &mut self.tile.buf,
+                offset,
+                stride,
+                frame,
+                rect,
+                max_iter,
+                &self.palette,
+            ),
+        }
     }
 }
 
 // Javascript jams
+// One off render, Renderer avoids the setup on every frame
 #[wasm_bindgen]
 pub fn render(
     ctx: &CanvasRenderingContext2d,
     width: usize,
     height: usize,
     max_iter: u32,
-    center_re: f32,
-    center_im: f32,
-    viewport_width: f32,
+    center_re: f64,
+    center_im: f64,
+    viewport_width: f64,
 ) -> Result<(), JsValue> {
-    dbg!("Rendering a {}x{} fractal", width, height);
-    let mut tile = TileBuffer::with_size(width, height);
-    render_tile(&mut tile, max_iter, center_re, center_im, viewport_width);
-    let data = ImageData::new_with_u8_clamped_array_and_sh(
-        Clamped(tile.get_mut_buf()),
-        width as u32,
-        height as u32,
-    )?;
-    ctx.put_image_data(&data, 0.0, 0.0)
-}
-
-// We split this out so that we can escape 'unsafe' as quickly
-// as possible.
-fn render_tile(
-    tile: &mut TileBuffer,
-    max_iter: u32,
-    center_re: f32,
-    center_im: f32,
-    viewport_width: f32,
-) {
-    let width = tile.w;
-    let height = tile.h;
-
-    let step = (viewport_width / width as f32) as f64;
-    let start_re = (center_re - viewport_width / 2.0) as f64;
-    let start_im = (center_im - (viewport_width * (height as f32 / width as f32)) / 2.0) as f64;
-    let blue = RGB::rgb(0, 183, 255);
-    let orange = RGB::rgb(255, 128, 0);
-    let black = RGB::rgb(0, 0, 0);
-    let white = RGB::rgb(255, 255, 255);
-
-    let gradients = vec![
-        [&black, &blue],
-        [&blue, &white],
-        [&white, &orange],
-        [&orange, &black],
-    ];
-
-    let palette = build_palette(&gradients, 4);
-
-    for y in 0..height {
-        for x in 0..width {
-            tile.buf.push(mandel_color(
-                mandel_iter(
-                    max_iter as u64,
-                    Complex {
-                        re: start_re + ((x as f64) * step),
-                        im: start_im + ((y as f64) * step),
-                    },
-                ),
-                &palette,
-            ));
-        }
-    }
+    let mut renderer = Renderer::new(width, height);
+    renderer.set_max_iter(max_iter);
+    renderer.set_view(center_re, center_im, viewport_width);
+    renderer.draw(ctx)
 }
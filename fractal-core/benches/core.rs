@@ -0,0 +1,97 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use fractal_core::{
+    default_palette, mandel_iter, mandel_iter_x2, render_frame, shift_pixels, Complex, Lanes,
+    Portable, Progressive, TileCache, Viewport, BOTTOM,
+};
+use std::hint::black_box;
+
+// The logic the wasm renderers share, compiled natively. cargo bench -p
+// fractal-core, with --features simd for the two lane render_frame.
+
+const MAX_ITER: u64 = 1000;
+const WIDTH: u32 = 256;
+const HEIGHT: u32 = 192;
+
+fn points(n: usize) -> Vec<Complex> {
+    let mut points = Vec::with_capacity(n * n);
+    for y in 0..n {
+        for x in 0..n {
+            points.push(Complex {
+                re: -2.0 + 2.5 * x as f64 / n as f64,
+                im: -1.25 + 2.5 * y as f64 / n as f64,
+            });
+        }
+    }
+    points
+}
+
+fn escapes(c: &mut Criterion) {
+    let points = points(64);
+    let mut group = c.benchmark_group("escapes");
+    group.throughput(Throughput::Elements(points.len() as u64));
+    group.bench_function("scalar", |b| {
+        b.iter(|| {
+            for p in &points {
+                black_box(mandel_iter(MAX_ITER, *p));
+            }
+        })
+    });
+    group.bench_function("portable x2", |b| {
+        b.iter(|| {
+            for pair in points.chunks_exact(2) {
+                black_box(mandel_iter_x2::<Portable>(MAX_ITER, [pair[0], pair[1]]));
+            }
+        })
+    });
+    group.bench_function("lanes x2", |b| {
+        b.iter(|| {
+            for pair in points.chunks_exact(2) {
+                black_box(mandel_iter_x2::<Lanes>(MAX_ITER, [pair[0], pair[1]]));
+            }
+        })
+    });
+    group.finish();
+}
+
+fn frames(c: &mut Criterion) {
+    let palette = default_palette();
+    let frame = Viewport::DEFAULT.frame(WIDTH, HEIGHT);
+    let mut buf = vec![BOTTOM; (WIDTH * HEIGHT) as usize];
+    let mut group = c.benchmark_group("frame");
+    group.throughput(Throughput::Elements(buf.len() as u64));
+    group.bench_function("render_frame", |b| {
+        b.iter(|| render_frame(&mut buf, WIDTH, HEIGHT, &frame, MAX_ITER, &palette))
+    });
+    for coarse in [false, true].iter() {
+        let name = if *coarse { "coarse to fine" } else { "rows" };
+        group.bench_function(BenchmarkId::new("progressive", name), |b| {
+            b.iter(|| {
+                let mut render = Progressive::new(frame, WIDTH, HEIGHT, MAX_ITER, *coarse);
+                while !render.step(&mut buf, &palette, 4096) {}
+            })
+        });
+    }
+    group.finish();
+}
+
+// Compositing once every tile is cached, and panning by shifting pixels
+fn reuse(c: &mut Criterion) {
+    let palette = default_palette();
+    let frame = Viewport::DEFAULT.frame(WIDTH, HEIGHT);
+    let mut buf = vec![BOTTOM; (WIDTH * HEIGHT) as usize];
+    let mut cache = TileCache::new(128, MAX_ITER, 64);
+    cache.render(&mut buf, WIDTH, HEIGHT, &frame, &palette, usize::MAX);
+
+    let mut group = c.benchmark_group("reuse");
+    group.throughput(Throughput::Elements(buf.len() as u64));
+    group.bench_function("cached tiles", |b| {
+        b.iter(|| cache.render(&mut buf, WIDTH, HEIGHT, &frame, &palette, 0))
+    });
+    group.bench_function("shift pixels", |b| {
+        b.iter(|| black_box(shift_pixels(&mut buf, WIDTH, HEIGHT, 3, -2)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, escapes, frames, reuse);
+criterion_main!(benches);
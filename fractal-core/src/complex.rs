@@ -0,0 +1,33 @@
+use core::ops::Add;
+
+// Complex coordination
+// https://rustwasm.github.io/wasm-bindgen/examples/julia.html
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    pub fn square(self) -> Complex {
+        let re = (self.re * self.re) - (self.im * self.im);
+        let im = 2.0 * self.re * self.im;
+        Complex { re, im }
+    }
+
+    // Squared magnitude, compare against 4.0 rather than 2.0
+    pub fn norm(&self) -> f64 {
+        (self.re * self.re) + (self.im * self.im)
+    }
+}
+
+impl Add<Complex> for Complex {
+    type Output = Complex;
+
+    fn add(self, rhs: Complex) -> Complex {
+        Complex {
+            re: self.re + rhs.re,
+            im: self.im + rhs.im,
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use crate::Complex;
+
+// Two f64 lanes, with just the operations the inner loop needs.
+//
+// Every backend does the same IEEE operations in the same order as the scalar
+// Complex maths, so the lanes give bit-identical escape counts.
+pub trait F64x2: Copy {
+    fn new(a: f64, b: f64) -> Self;
+    fn splat(v: f64) -> Self;
+    fn add(self, rhs: Self) -> Self;
+    fn sub(self, rhs: Self) -> Self;
+    fn mul(self, rhs: Self) -> Self;
+    // Bit i is set when lane i of self <= rhs
+    fn le(self, rhs: Self) -> u8;
+    // Bit i is set when lane i of self > rhs
+    fn gt(self, rhs: Self) -> u8;
+}
+
+// Plain array, for targets without a SIMD backend
+#[derive(Clone, Copy, Debug)]
+pub struct Portable([f64; 2]);
+
+impl F64x2 for Portable {
+    fn new(a: f64, b: f64) -> Self {
+        Portable([a, b])
+    }
+    fn splat(v: f64) -> Self {
+        Portable([v, v])
+    }
+    fn add(self, rhs: Self) -> Self {
+        Portable([self.0[0] + rhs.0[0], self.0[1] + rhs.0[1]])
+    }
+    fn sub(self, rhs: Self) -> Self {
+        Portable([self.0[0] - rhs.0[0], self.0[1] - rhs.0[1]])
+    }
+    fn mul(self, rhs: Self) -> Self {
+        Portable([self.0[0] * rhs.0[0], self.0[1] * rhs.0[1]])
+    }
+    fn le(self, rhs: Self) -> u8 {
+        (self.0[0] <= rhs.0[0]) as u8 | ((self.0[1] <= rhs.0[1]) as u8) << 1
+    }
+    fn gt(self, rhs: Self) -> u8 {
+        (self.0[0] > rhs.0[0]) as u8 | ((self.0[1] > rhs.0[1]) as u8) << 1
+    }
+}
+
+// wasm simd128, needs RUSTFLAGS="-C target-feature=+simd128"
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+mod simd128 {
+    use super::F64x2;
+    use core::arch::wasm32::*;
+
+    #[derive(Clone, Copy, Debug)]
+    pub struct Simd128(v128);
+
+    impl F64x2 for Simd128 {
+        fn new(a: f64, b: f64) -> Self {
+            Simd128(f64x2(a, b))
+        }
+        fn splat(v: f64) -> Self {
+            Simd128(f64x2_splat(v))
+        }
+        fn add(self, rhs: Self) -> Self {
+            Simd128(f64x2_add(self.0, rhs.0))
+        }
+        fn sub(self, rhs: Self) -> Self {
+            Simd128(f64x2_sub(self.0, rhs.0))
+        }
+        fn mul(self, rhs: Self) -> Self {
+            Simd128(f64x2_mul(self.0, rhs.0))
+        }
+        fn le(self, rhs: Self) -> u8 {
+            i64x2_bitmask(f64x2_le(self.0, rhs.0))
+        }
+        fn gt(self, rhs: Self) -> u8 {
+            i64x2_bitmask(f64x2_gt(self.0, rhs.0))
+        }
+    }
+}
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+pub use self::simd128::Simd128;
+
+// SSE2 is part of x86_64, so this needs no runtime detection
+#[cfg(target_arch = "x86_64")]
+mod sse2 {
+    #![allow(unused_unsafe)]
+    use super::F64x2;
+    use core::arch::x86_64::*;
+
+    #[derive(Clone, Copy, Debug)]
+    pub struct Sse2(__m128d);
+
+    impl F64x2 for Sse2 {
+        fn new(a: f64, b: f64) -> Self {
+            // _mm_set_pd takes the high lane first
+            Sse2(unsafe { _mm_set_pd(b, a) })
+        }
+        fn splat(v: f64) -> Self {
+            Sse2(unsafe { _mm_set1_pd(v) })
+        }
+        fn add(self, rhs: Self) -> Self {
+            Sse2(unsafe { _mm_add_pd(self.0, rhs.0) })
+        }
+        fn sub(self, rhs: Self) -> Self {
+            Sse2(unsafe { _mm_sub_pd(self.0, rhs.0) })
+        }
+        fn mul(self, rhs: Self) -> Self {
+            Sse2(unsafe { _mm_mul_pd(self.0, rhs.0) })
+        }
+        fn le(self, rhs: Self) -> u8 {
+            unsafe { _mm_movemask_pd(_mm_cmple_pd(self.0, rhs.0)) as u8 }
+        }
+        fn gt(self, rhs: Self) -> u8 {
+            unsafe { _mm_movemask_pd(_mm_cmpgt_pd(self.0, rhs.0)) as u8 }
+        }
+    }
+}
+#[cfg(target_arch = "x86_64")]
+pub use self::sse2::Sse2;
+
+// The fastest lanes the target was compiled for
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+pub type Lanes = Simd128;
+#[cfg(target_arch = "x86_64")]
+pub type Lanes = Sse2;
+#[cfg(not(any(
+    all(target_arch = "wasm32", target_feature = "simd128"),
+    target_arch = "x86_64"
+)))]
+pub type Lanes = Portable;
+
+// mandel_iter for two points at once. Lanes that escape are masked out and
+// keep their count while the other lane carries on.
+pub fn mandel_iter_x2<V: F64x2>(max_iter: u64, c: [Complex; 2]) -> [Option<u64>; 2] {
+    let mut escapes = [None, None];
+    if max_iter == 0 {
+        return escapes;
+    }
+
+    let c_re = V::new(c[0].re, c[1].re);
+    let c_im = V::new(c[0].im, c[1].im);
+    let two = V::splat(2.0);
+    let four = V::splat(4.0);
+    let (mut re, mut im) = (c_re, c_im);
+    let mut active = 0b11;
+    let mut iter = 1;
+
+    loop {
+        let norm = re.mul(re).add(im.mul(im));
+        let escaped = active & norm.gt(four);
+        for (lane, escape) in escapes.iter_mut().enumerate() {
+            if escaped & (1 << lane) != 0 {
+                *escape = Some(iter);
+            }
+        }
+        // A NaN norm neither escapes nor stays, like the scalar loop
+        active &= norm.le(four);
+        if active == 0 || iter >= max_iter {
+            return escapes;
+        }
+
+        // z = c + z^2, in Complex::square's order
+        let next_re = re.mul(re).sub(im.mul(im));
+        let next_im = two.mul(re).mul(im);
+        re = c_re.add(next_re);
+        im = c_im.add(next_im);
+        iter += 1;
+    }
+}
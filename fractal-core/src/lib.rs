@@ -0,0 +1,32 @@
+// Fractal maths shared by fractal-rs and the wasm renderers
+//
+// no_std so that bare-metal-fractal can use it, alloc is only needed for
+// building palettes.
+#![no_std]
+
+extern crate alloc;
+
+mod complex;
+mod lanes;
+mod mandel;
+mod palette;
+mod progressive;
+mod scene;
+mod tiles;
+mod viewport;
+
+pub use complex::Complex;
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+pub use lanes::Simd128;
+#[cfg(target_arch = "x86_64")]
+pub use lanes::Sse2;
+pub use lanes::{mandel_iter_x2, F64x2, Lanes, Portable};
+pub use mandel::{mandel_iter, mandel_orbit};
+pub use palette::{build_palette, default_palette, mandel_color, tween_one, BOTTOM, RGB};
+pub use progressive::{Progressive, COARSE_BLOCK};
+pub use scene::{Colouring, Formula, Scene, SCENE_WIDTH};
+pub use tiles::{TileCache, TileKey, MAX_ZOOM};
+pub use viewport::{
+    colour_iterations, escape_to_raw, iterate_rect, raw_to_escape, render_frame, render_rect,
+    shift_pixels, Frame, Rect, Viewport, INTERIOR,
+};
@@ -0,0 +1,41 @@
+use crate::Complex;
+
+// Mandelbrot maths
+// See https://en.wikipedia.org/wiki/Mandelbrot_set
+//
+// Escape count contract, shared by every renderer:
+// * The orbit is z_1 = c, z_(n+1) = z_n^2 + c, so c itself is the first
+//   iteration and a point with |c| > 2 escapes at 1.
+// * A point escapes at n, the first n in 1..=max_iter with |z_n| > 2.
+// * Points that haven't escaped by max_iter are interior, which is None
+//   rather than a magic count.
+
+// The inner iteration loop, returning the iteration count and the last z so
+// that callers can derive smooth values from it. The point escaped iff
+// z.norm() > 4.0.
+pub fn mandel_orbit(max_iter: u64, c: Complex) -> (u64, Complex) {
+    let mut z: Complex = c;
+
+    let mut iter = 1;
+
+    while (z.norm() <= 4.0) && (iter < max_iter) {
+        z = c + z.square();
+        iter += 1;
+    }
+
+    (iter, z)
+}
+
+// Iteration at which c escapes, None inside the set
+pub fn mandel_iter(max_iter: u64, c: Complex) -> Option<u64> {
+    if max_iter == 0 {
+        return None;
+    }
+    let (iter, z) = mandel_orbit(max_iter, c);
+
+    if z.norm() > 4.0 {
+        Some(iter)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,80 @@
+use alloc::vec::Vec;
+
+// With this byte order javascript can copy it straight into canvas
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RGB {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+pub fn tween_one(progress: i32, from: u8, to: u8) -> u8 {
+    let from = from as i32;
+    let to = to as i32;
+    (from + (to - from) * progress / 255) as u8
+}
+
+impl RGB {
+    #[allow(clippy::self_named_constructors)]
+    pub fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b, a: 255 }
+    }
+
+    pub fn tween(&self, progress: i32, to: &RGB) -> RGB {
+        RGB::rgb(
+            tween_one(progress, self.r, to.r),
+            tween_one(progress, self.g, to.g),
+            tween_one(progress, self.b, to.b),
+        )
+    }
+}
+
+pub static BOTTOM: RGB = RGB {
+    r: 0,
+    g: 0,
+    b: 0,
+    a: 255,
+};
+
+// golfing to do here...
+pub fn build_palette(gradients: &[[&RGB; 2]], steps_per_grad: usize) -> Vec<RGB> {
+    let mut palette = Vec::with_capacity(gradients.len() * steps_per_grad);
+    for [color, next_color] in gradients {
+        for step in 0..steps_per_grad {
+            let progress = (step * 255 / steps_per_grad) as i32;
+            palette.push(color.tween(progress, next_color));
+        }
+    }
+    palette
+}
+
+// Black to blue to white to orange and back to black
+pub fn default_palette() -> Vec<RGB> {
+    let blue = RGB::rgb(0, 183, 255);
+    let orange = RGB::rgb(255, 128, 0);
+    let black = RGB::rgb(0, 0, 0);
+    let white = RGB::rgb(255, 255, 255);
+
+    build_palette(
+        &[
+            [&black, &blue],
+            [&blue, &white],
+            [&white, &orange],
+            [&orange, &black],
+        ],
+        4,
+    )
+}
+
+pub fn mandel_color(escape: Option<u64>, palette: &[RGB]) -> RGB {
+    match escape {
+        None => BOTTOM,
+        // This is on the hot loop, can len be removed?
+        Some(i) => match palette.len() {
+            0 => BOTTOM,
+            len => palette[(i % len as u64) as usize],
+        },
+    }
+}
@@ -0,0 +1,121 @@
+use crate::{escape_to_raw, mandel_color, mandel_iter, Frame, RGB};
+
+// Block size of the first coarse to fine pass, each later pass halves it
+pub const COARSE_BLOCK: u32 = 16;
+
+// Renders a frame a few pixels at a time so callers can yield in between.
+//
+// Coarse to fine starts with one sample per COARSE_BLOCK square, filling the
+// whole square with it, then interlaces passes of half the block size until
+// every pixel has its own sample. Every pixel is still sampled exactly once.
+#[derive(Clone, Copy, Debug)]
+pub struct Progressive {
+    frame: Frame,
+    width: u32,
+    height: u32,
+    max_iter: u64,
+    first_block: u32,
+    block: u32, // 0 once finished
+    x: u32,
+    y: u32,
+    sampled: u64,
+}
+
+impl Progressive {
+    pub fn new(frame: Frame, width: u32, height: u32, max_iter: u64, coarse: bool) -> Self {
+        let block = if coarse { COARSE_BLOCK } else { 1 };
+        Progressive {
+            frame,
+            width,
+            height,
+            max_iter,
+            first_block: block,
+            block: if width == 0 || height == 0 { 0 } else { block },
+            x: 0,
+            y: 0,
+            sampled: 0,
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.block == 0
+    }
+
+    // Fraction of pixels sampled so far
+    pub fn progress(&self) -> f64 {
+        if self.finished() {
+            1.0
+        } else {
+            self.sampled as f64 / (self.width as f64 * self.height as f64)
+        }
+    }
+
+    // Samples at most budget pixels into buf, returns true once finished
+    pub fn step(&mut self, buf: &mut [RGB], palette: &[RGB], budget: u32) -> bool {
+        self.step_with(budget, |from, to, escape| {
+            let colour = mandel_color(escape, palette);
+            for pixel in &mut buf[from..to] {
+                *pixel = colour;
+            }
+        })
+    }
+
+    // step that also fills iters with raw escape counts, see iterate_rect
+    pub fn step_with_iterations(
+        &mut self,
+        buf: &mut [RGB],
+        iters: &mut [u32],
+        palette: &[RGB],
+        budget: u32,
+    ) -> bool {
+        self.step_with(budget, |from, to, escape| {
+            let colour = mandel_color(escape, palette);
+            for pixel in &mut buf[from..to] {
+                *pixel = colour;
+            }
+            let raw = escape_to_raw(escape);
+            for iter in &mut iters[from..to] {
+                *iter = raw;
+            }
+        })
+    }
+
+    // Calls fill with each range of buffer indices and the escape to fill
+    // them with
+    fn step_with(&mut self, budget: u32, mut fill: impl FnMut(usize, usize, Option<u64>)) -> bool {
+        let mut budget = budget;
+        while !self.finished() {
+            let (x, y, block) = (self.x, self.y, self.block);
+            // Already sampled by the previous, coarser, pass
+            let done = block < self.first_block && x % (2 * block) == 0 && y % (2 * block) == 0;
+            if !done {
+                if budget == 0 {
+                    break;
+                }
+                let escape = mandel_iter(self.max_iter, self.frame.pixel(x, y));
+                for fill_y in y..(y + block).min(self.height) {
+                    let row = (fill_y * self.width) as usize;
+                    let from = row + x as usize;
+                    let to = row + (x + block).min(self.width) as usize;
+                    fill(from, to, escape);
+                }
+                self.sampled += 1;
+                budget -= 1;
+            }
+            self.advance();
+        }
+        self.finished()
+    }
+
+    fn advance(&mut self) {
+        self.x += self.block;
+        if self.x >= self.width {
+            self.x = 0;
+            self.y += self.block;
+        }
+        if self.y >= self.height {
+            self.y = 0;
+            self.block /= 2;
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+
+use crate::RGB;
+
+// Width of the real axis across the frame at zoom 0, zoom z shows
+// SCENE_WIDTH / 2^z like fractal-rs' renderer with a tile_width of 3
+pub const SCENE_WIDTH: f64 = 3.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Formula {
+    Mandelbrot,
+}
+
+impl Formula {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Formula::Mandelbrot => "mandelbrot",
+        }
+    }
+}
+
+impl FromStr for Formula {
+    type Err = String;
+
+    // A name and its parameters, the Mandelbrot set has none
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let formula = match words.next() {
+            Some("mandelbrot") => Formula::Mandelbrot,
+            other => return Err(format!("unknown formula {:?}", other.unwrap_or(""))),
+        };
+        match words.next() {
+            None => Ok(formula),
+            Some(p) => Err(format!(
+                "{} takes no parameters, got {:?}",
+                formula.name(),
+                p
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Colouring {
+    Smooth, // continuous escape counts blending palette entries, as fractal-rs
+    Bands,  // a palette entry per escape count, as the wasm renderers
+}
+
+impl Colouring {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Colouring::Smooth => "smooth",
+            Colouring::Bands => "bands",
+        }
+    }
+}
+
+impl FromStr for Colouring {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "smooth" => Ok(Colouring::Smooth),
+            "bands" => Ok(Colouring::Bands),
+            _ => Err(format!("unknown colouring {:?}", s)),
+        }
+    }
+}
+
+// Everything needed to render an image again, as a text file of
+// "key value..." lines with # comments:
+//
+//   formula mandelbrot
+//   center -0.743643887037151 0.13182590420533
+//   zoom 20
+//   max_iter 1000
+//   size 1920 1080
+//   supersample 2
+//   colouring smooth
+//   palette 000764 206bcb edffff ffaa00 000200
+//
+// supersample (n x n samples a pixel) and colouring may be left out.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scene {
+    pub formula: Formula,
+    // Decimal strings kept as written, so files round trip exactly. They're
+    // still limited to what an f64 holds, as is everything rendering them
+    pub center_re: String,
+    pub center_im: String,
+    pub zoom: String,
+    pub max_iter: u64,
+    pub width: u32,
+    pub height: u32,
+    pub supersample: u32,
+    pub colouring: Colouring,
+    pub palette: Vec<RGB>,
+}
+
+impl fmt::Display for Scene {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "formula {}", self.formula.name())?;
+        writeln!(f, "center {} {}", self.center_re, self.center_im)?;
+        writeln!(f, "zoom {}", self.zoom)?;
+        writeln!(f, "max_iter {}", self.max_iter)?;
+        writeln!(f, "size {} {}", self.width, self.height)?;
+        writeln!(f, "supersample {}", self.supersample)?;
+        writeln!(f, "colouring {}", self.colouring.name())?;
+        write!(f, "palette")?;
+        for c in &self.palette {
+            write!(f, " {:02x}{:02x}{:02x}", c.r, c.g, c.b)?;
+        }
+        writeln!(f)
+    }
+}
+
+impl FromStr for Scene {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut formula = None;
+        let mut center = None;
+        let mut zoom = None;
+        let mut max_iter = None;
+        let mut size = None;
+        let mut supersample = 1;
+        let mut colouring = Colouring::Smooth;
+        let mut palette = None;
+
+        for (n, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = match line.find(char::is_whitespace) {
+                Some(i) => (&line[..i], line[i..].trim()),
+                None => (line, ""),
+            };
+            let at = |e: String| format!("line {}: {}", n + 1, e);
+            match key {
+                "formula" => formula = Some(value.parse().map_err(at)?),
+                "center" => match pair(value) {
+                    Some((re, im)) => {
+                        center = Some((decimal(re).map_err(at)?, decimal(im).map_err(at)?))
+                    }
+                    None => return Err(at(format!("expected center re im, got {:?}", value))),
+                },
+                "zoom" => zoom = Some(decimal(value).map_err(at)?),
+                "max_iter" => max_iter = Some(number(value).map_err(at)?),
+                "size" => match pair(value) {
+                    Some((w, h)) => size = Some((number(w).map_err(at)?, number(h).map_err(at)?)),
+                    None => return Err(at(format!("expected size width height, got {:?}", value))),
+                },
+                "supersample" => supersample = number(value).map_err(at)?,
+                "colouring" => colouring = value.parse().map_err(at)?,
+                "palette" => {
+                    let colours = value
+                        .split_whitespace()
+                        .map(hex)
+                        .collect::<Result<Vec<RGB>, String>>();
+                    palette = Some(colours.map_err(at)?);
+                }
+                _ => return Err(at(format!("unknown key {:?}", key))),
+            }
+        }
+
+        let missing = |key: &str| format!("missing {}", key);
+        let (center_re, center_im) = center.ok_or_else(|| missing("center"))?;
+        let (width, height) = size.ok_or_else(|| missing("size"))?;
+        let palette: Vec<RGB> = palette.ok_or_else(|| missing("palette"))?;
+        if width == 0 || height == 0 {
+            return Err(format!("size {}x{} is empty", width, height));
+        }
+        if supersample == 0 {
+            return Err("supersample must be at least 1".to_string());
+        }
+        if palette.is_empty() {
+            return Err("palette has no colours".to_string());
+        }
+        Ok(Scene {
+            formula: formula.ok_or_else(|| missing("formula"))?,
+            center_re,
+            center_im,
+            zoom: zoom.ok_or_else(|| missing("zoom"))?,
+            max_iter: max_iter.ok_or_else(|| missing("max_iter"))?,
+            width,
+            height,
+            supersample,
+            colouring,
+            palette,
+        })
+    }
+}
+
+// Exactly two words
+fn pair(s: &str) -> Option<(&str, &str)> {
+    let mut words = s.split_whitespace();
+    match (words.next(), words.next(), words.next()) {
+        (Some(a), Some(b), None) => Some((a, b)),
+        _ => None,
+    }
+}
+
+// A decimal within f64's range, kept as text
+fn decimal(s: &str) -> Result<String, String> {
+    match s.parse::<f64>() {
+        Ok(v) if v.is_finite() => Ok(s.to_string()),
+        _ => Err(format!("{:?} isn't a number", s)),
+    }
+}
+
+fn number<T: FromStr>(s: &str) -> Result<T, String> {
+    s.parse()
+        .map_err(|_| format!("{:?} isn't a whole number", s))
+}
+
+// rrggbb
+fn hex(s: &str) -> Result<RGB, String> {
+    let channel = |i: usize| s.get(i..i + 2).and_then(|c| u8::from_str_radix(c, 16).ok());
+    match (s.len(), channel(0), channel(2), channel(4)) {
+        (6, Some(r), Some(g), Some(b)) => Ok(RGB::rgb(r, g, b)),
+        _ => Err(format!("{:?} isn't an rrggbb colour", s)),
+    }
+}
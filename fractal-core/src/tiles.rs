@@ -0,0 +1,278 @@
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use crate::{iterate_rect, mandel_color, raw_to_escape, Complex, Frame, Rect, INTERIOR, RGB};
+
+// Deepest level tiles are made at, past it f64 runs out of precision anyway
+pub const MAX_ZOOM: u32 = 48;
+
+// How many coarser levels a missing tile looks through for a stand in
+const FALLBACK_LEVELS: u32 = 8;
+
+// A tile in the same scheme as fractal-rs' TileSpace. Tile (x, y) at zoom z
+// spans [x, x + 1) / 2^z by [y, y + 1) / 2^z of the complex plane.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TileKey {
+    pub x: i64,
+    pub y: i64,
+    pub zoom: u32,
+}
+
+struct Cached {
+    iters: Vec<u32>, // raw escape counts, size x size
+    used: u64,
+}
+
+// Square tiles of size x size texels, least recently used ones dropped past
+// capacity. Tiles visible in the frame being rendered are never dropped, so
+// a frame needing more than capacity tiles holds them all until the view
+// moves on. Tiles hold raw escape counts so palettes can change freely, a
+// change of max_iter empties the cache.
+//
+// Frames are composited from the finest level whose texels are no wider than
+// a pixel, so they're resampled rather than exact renders. Tiles that aren't
+// made yet show the closest coarser tile that is.
+pub struct TileCache {
+    size: u32,
+    max_iter: u64,
+    capacity: usize,
+    tiles: BTreeMap<TileKey, Cached>,
+    clock: u64,
+}
+
+impl TileCache {
+    pub fn new(size: u32, max_iter: u64, capacity: usize) -> Self {
+        TileCache {
+            size,
+            max_iter,
+            capacity,
+            tiles: BTreeMap::new(),
+            clock: 0,
+        }
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn max_iter(&self) -> u64 {
+        self.max_iter
+    }
+
+    pub fn set_max_iter(&mut self, max_iter: u64) {
+        if max_iter != self.max_iter {
+            self.max_iter = max_iter;
+            self.tiles.clear();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+
+    pub fn contains(&self, key: &TileKey) -> bool {
+        self.tiles.contains_key(key)
+    }
+
+    // Level whose texels are no wider than the frame's pixels
+    pub fn level(&self, frame: &Frame) -> u32 {
+        let mut zoom = 0;
+        let mut texel = 1.0 / self.size as f64;
+        while texel > frame.step && zoom < MAX_ZOOM {
+            zoom += 1;
+            texel /= 2.0;
+        }
+        zoom
+    }
+
+    // Tiles at level covering a width x height frame, closest to its centre
+    // first
+    pub fn visible(&self, frame: &Frame, width: u32, height: u32) -> Vec<TileKey> {
+        if width == 0 || height == 0 {
+            return Vec::new();
+        }
+        let zoom = self.level(frame);
+        let scale = (1u64 << zoom) as f64;
+        let first = frame.pixel(0, 0);
+        let last = frame.pixel(width - 1, height - 1);
+        let (x0, x1) = (floor(first.re * scale), floor(last.re * scale));
+        let (y0, y1) = (floor(first.im * scale), floor(last.im * scale));
+
+        let mut keys = Vec::new();
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                keys.push(TileKey { x, y, zoom });
+            }
+        }
+        let (cx, cy) = (x0 + x1, y0 + y1);
+        keys.sort_by_key(|k| (2 * k.x - cx).abs() + (2 * k.y - cy).abs());
+        keys
+    }
+
+    // Iterates a tile into the cache unless it's there already, dropping the
+    // least recently used tiles past capacity
+    pub fn generate(&mut self, key: TileKey) {
+        self.make(key);
+        self.evict(&[]);
+    }
+
+    fn make(&mut self, key: TileKey) {
+        self.clock += 1;
+        if let Some(tile) = self.tiles.get_mut(&key) {
+            tile.used = self.clock;
+            return;
+        }
+
+        let size = self.size;
+        let scale = (1u64 << key.zoom) as f64;
+        let frame = Frame {
+            start: Complex {
+                re: key.x as f64 / scale,
+                im: key.y as f64 / scale,
+            },
+            step: 1.0 / scale / size as f64,
+        };
+        let rect = Rect {
+            x: 0,
+            y: 0,
+            width: size,
+            height: size,
+        };
+        let mut iters = alloc::vec![0; (size * size) as usize];
+        iterate_rect(&mut iters, 0, size as usize, &frame, rect, self.max_iter);
+        self.tiles.insert(
+            key,
+            Cached {
+                iters,
+                used: self.clock,
+            },
+        );
+    }
+
+    // Drops least recently used tiles past capacity, other than pinned ones
+    fn evict(&mut self, pinned: &[TileKey]) {
+        while self.tiles.len() > self.capacity {
+            let oldest = self
+                .tiles
+                .iter()
+                .filter(|(k, _)| !pinned.contains(k))
+                .min_by_key(|(_, t)| t.used)
+                .map(|(k, _)| *k);
+            match oldest {
+                Some(k) => self.tiles.remove(&k),
+                None => break,
+            };
+        }
+    }
+
+    // Makes at most budget of the frame's missing tiles and composites it
+    // into buf, returning how many are still missing
+    pub fn render(
+        &mut self,
+        buf: &mut [RGB],
+        width: u32,
+        height: u32,
+        frame: &Frame,
+        palette: &[RGB],
+        budget: usize,
+    ) -> usize {
+        self.render_with(width, height, frame, budget, |i, raw| {
+            buf[i] = mandel_color(raw_to_escape(raw), palette);
+        })
+    }
+
+    // render that also fills iters with the composited raw escape counts
+    #[allow(clippy::too_many_arguments)]
+    pub fn render_with_iterations(
+        &mut self,
+        buf: &mut [RGB],
+        iters: &mut [u32],
+        width: u32,
+        height: u32,
+        frame: &Frame,
+        palette: &[RGB],
+        budget: usize,
+    ) -> usize {
+        self.render_with(width, height, frame, budget, |i, raw| {
+            buf[i] = mandel_color(raw_to_escape(raw), palette);
+            iters[i] = raw;
+        })
+    }
+
+    fn render_with(
+        &mut self,
+        width: u32,
+        height: u32,
+        frame: &Frame,
+        mut budget: usize,
+        mut fill: impl FnMut(usize, u32),
+    ) -> usize {
+        let visible = self.visible(frame, width, height);
+        for key in visible.iter() {
+            if !self.contains(key) {
+                if budget == 0 {
+                    continue;
+                }
+                budget -= 1;
+            }
+            self.make(*key);
+        }
+        let zoom = match visible.first() {
+            Some(key) => key.zoom,
+            None => return 0,
+        };
+        let missing = visible.iter().filter(|k| !self.contains(k)).count();
+
+        // Neighbouring pixels mostly share a tile
+        let mut last: Option<(TileKey, &Cached)> = None;
+        for y in 0..height {
+            for x in 0..width {
+                let c = frame.pixel(x, y);
+                let mut raw = INTERIOR;
+                for level in (zoom.saturating_sub(FALLBACK_LEVELS)..=zoom).rev() {
+                    let (key, texel) = self.locate(c, level);
+                    let tile = match last {
+                        Some((k, tile)) if k == key => Some(tile),
+                        _ => self.tiles.get(&key),
+                    };
+                    if let Some(tile) = tile {
+                        raw = tile.iters[texel];
+                        last = Some((key, tile));
+                        break;
+                    }
+                }
+                fill((y * width + x) as usize, raw);
+            }
+        }
+        self.evict(&visible);
+        missing
+    }
+
+    // The tile at level holding c, and the index of its texel there
+    fn locate(&self, c: Complex, level: u32) -> (TileKey, usize) {
+        let scale = (1u64 << level) as f64;
+        let (re, im) = (c.re * scale, c.im * scale);
+        let (x, y) = (floor(re), floor(im));
+        let texel = |t: f64, i: i64| ((t - i as f64) * self.size as f64) as u32;
+        let (tx, ty) = (
+            texel(re, x).min(self.size - 1),
+            texel(im, y).min(self.size - 1),
+        );
+        let key = TileKey { x, y, zoom: level };
+        (key, (ty * self.size + tx) as usize)
+    }
+}
+
+// f64::floor is std only
+fn floor(t: f64) -> i64 {
+    let i = t as i64;
+    if (i as f64) > t {
+        i - 1
+    } else {
+        i
+    }
+}
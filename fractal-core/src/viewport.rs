@@ -0,0 +1,285 @@
+use crate::{mandel_color, mandel_iter, Complex, RGB};
+#[cfg(feature = "simd")]
+use crate::{mandel_iter_x2, Lanes};
+
+// What part of the complex plane a frame shows
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    pub center_re: f64,
+    pub center_im: f64,
+    pub width: f64, // in complex space, the height follows the frame aspect
+}
+
+// A viewport mapped onto a frame of pixels
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frame {
+    pub start: Complex, // top left pixel
+    pub step: f64,      // distance between pixels
+}
+
+impl Viewport {
+    // The whole set, where the demos start
+    pub const DEFAULT: Viewport = Viewport {
+        center_re: -0.5,
+        center_im: 0.0,
+        width: 3.0,
+    };
+
+    pub fn frame(&self, width: u32, height: u32) -> Frame {
+        let step = self.width / width as f64;
+        let start = Complex {
+            re: self.center_re - self.width / 2.0,
+            im: self.center_im - (self.width * (height as f64 / width as f64)) / 2.0,
+        };
+        Frame { start, step }
+    }
+
+    // Moves the view so the image follows a drag of (dx, dy) pixels on a
+    // frame_width wide frame
+    pub fn pan(&mut self, frame_width: u32, dx: f64, dy: f64) {
+        let step = self.width / frame_width as f64;
+        self.center_re -= dx * step;
+        self.center_im -= dy * step;
+    }
+
+    // Zooms in by factor (out when below 1), keeping the point under pixel
+    // (x, y) where it is
+    pub fn zoom_at(&mut self, frame_width: u32, frame_height: u32, x: f64, y: f64, factor: f64) {
+        let frame = self.frame(frame_width, frame_height);
+        let re = frame.start.re + x * frame.step;
+        let im = frame.start.im + y * frame.step;
+        self.center_re = re + (self.center_re - re) / factor;
+        self.center_im = im + (self.center_im - im) / factor;
+        self.width /= factor;
+    }
+}
+
+impl Frame {
+    pub fn pixel(&self, x: u32, y: u32) -> Complex {
+        Complex {
+            re: self.start.re + ((x as f64) * self.step),
+            im: self.start.im + ((y as f64) * self.step),
+        }
+    }
+}
+
+// A rectangle of pixels within a frame
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    // Whether a buffer of len pixels can hold the rect at offset with stride
+    pub fn fits(&self, len: usize, offset: usize, stride: usize) -> bool {
+        if self.width == 0 || self.height == 0 {
+            return offset <= len;
+        }
+        (self.width as usize) <= stride
+            && offset + (self.height as usize - 1) * stride + self.width as usize <= len
+    }
+}
+
+// Colours a width x height frame into buf, row major
+pub fn render_frame(
+    buf: &mut [RGB],
+    width: u32,
+    height: u32,
+    frame: &Frame,
+    max_iter: u64,
+    palette: &[RGB],
+) {
+    let rect = Rect {
+        x: 0,
+        y: 0,
+        width,
+        height,
+    };
+    render_rect(buf, 0, width as usize, frame, rect, max_iter, palette);
+}
+
+// Colours part of a frame, pixel (rect.x + i, rect.y + j) going to
+// buf[offset + j * stride + i], so that several threads can each fill their
+// own part of one shared buffer
+pub fn render_rect(
+    buf: &mut [RGB],
+    offset: usize,
+    stride: usize,
+    frame: &Frame,
+    rect: Rect,
+    max_iter: u64,
+    palette: &[RGB],
+) {
+    for j in 0..rect.height {
+        let row = offset + j as usize * stride;
+        render_row(
+            &mut buf[row..row + rect.width as usize],
+            frame,
+            rect.x,
+            rect.y + j,
+            max_iter,
+            palette,
+        );
+    }
+}
+
+// Moves the contents of a row major width x height buffer by (dx, dy) pixels,
+// the way they move when the view is panned by that much, and returns the
+// strips left uncovered. Only those need rendering again.
+pub fn shift_pixels<T: Copy>(
+    buf: &mut [T],
+    width: u32,
+    height: u32,
+    dx: i32,
+    dy: i32,
+) -> [Rect; 2] {
+    let empty = Rect {
+        x: 0,
+        y: 0,
+        width: 0,
+        height: 0,
+    };
+    let (adx, ady) = (dx.wrapping_abs() as u32, dy.wrapping_abs() as u32);
+    if adx >= width || ady >= height {
+        let whole = Rect {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        };
+        return [whole, empty];
+    }
+
+    let w = width as usize;
+    let kept = (width - adx) as usize;
+    let (from_x, to_x) = if dx > 0 {
+        (0, adx as usize)
+    } else {
+        (adx as usize, 0)
+    };
+    // Walk rows away from where they're moving so none is overwritten first
+    let rows = height - ady;
+    for i in 0..rows {
+        let (from_y, to_y) = if dy > 0 {
+            (rows - 1 - i, height - 1 - i)
+        } else {
+            (ady + i, i)
+        };
+        let from = from_y as usize * w + from_x;
+        buf.copy_within(from..from + kept, to_y as usize * w + to_x);
+    }
+
+    // The exposed columns run the full height, the rows fill in the rest
+    let columns = Rect {
+        x: if dx > 0 { 0 } else { width - adx },
+        y: 0,
+        width: adx,
+        height,
+    };
+    let rows = Rect {
+        x: if dx > 0 { adx } else { 0 },
+        y: if dy > 0 { 0 } else { height - ady },
+        width: width - adx,
+        height: ady,
+    };
+    [columns, rows]
+}
+
+// Escape counts in raw buffers, where 0 can't be a count and marks interior
+// points
+pub const INTERIOR: u32 = 0;
+
+pub fn escape_to_raw(escape: Option<u64>) -> u32 {
+    match escape {
+        None => INTERIOR,
+        Some(n) => n.min(u32::MAX as u64) as u32,
+    }
+}
+
+pub fn raw_to_escape(raw: u32) -> Option<u64> {
+    match raw {
+        INTERIOR => None,
+        n => Some(n as u64),
+    }
+}
+
+// Like render_rect, but writes raw escape counts so the colours can be
+// changed later with colour_iterations without iterating again
+pub fn iterate_rect(
+    iters: &mut [u32],
+    offset: usize,
+    stride: usize,
+    frame: &Frame,
+    rect: Rect,
+    max_iter: u64,
+) {
+    for j in 0..rect.height {
+        let row = offset + j as usize * stride;
+        let row = &mut iters[row..row + rect.width as usize];
+        escape_row(
+            frame,
+            rect.x,
+            rect.y + j,
+            row.len(),
+            max_iter,
+            |i, escape| row[i] = escape_to_raw(escape),
+        );
+    }
+}
+
+// Colours raw escape counts, just as render_rect would have
+pub fn colour_iterations(iters: &[u32], buf: &mut [RGB], palette: &[RGB]) {
+    for (pixel, raw) in buf.iter_mut().zip(iters) {
+        *pixel = mandel_color(raw_to_escape(*raw), palette);
+    }
+}
+
+fn render_row(row: &mut [RGB], frame: &Frame, x: u32, y: u32, max_iter: u64, palette: &[RGB]) {
+    escape_row(frame, x, y, row.len(), max_iter, |i, escape| {
+        row[i] = mandel_color(escape, palette)
+    });
+}
+
+// Calls f with the escape of each of the width pixels from (x, y)
+#[cfg(not(feature = "simd"))]
+fn escape_row(
+    frame: &Frame,
+    x: u32,
+    y: u32,
+    width: usize,
+    max_iter: u64,
+    mut f: impl FnMut(usize, Option<u64>),
+) {
+    for i in 0..width {
+        f(i, mandel_iter(max_iter, frame.pixel(x + i as u32, y)));
+    }
+}
+
+// Two pixels at a time, with a scalar pixel at the end of odd rows
+#[cfg(feature = "simd")]
+fn escape_row(
+    frame: &Frame,
+    x: u32,
+    y: u32,
+    width: usize,
+    max_iter: u64,
+    mut f: impl FnMut(usize, Option<u64>),
+) {
+    let pairs = width / 2 * 2;
+    for i in (0..pairs).step_by(2) {
+        let px = x + i as u32;
+        let c = [frame.pixel(px, y), frame.pixel(px + 1, y)];
+        let escapes = mandel_iter_x2::<Lanes>(max_iter, c);
+        f(i, escapes[0]);
+        f(i + 1, escapes[1]);
+    }
+    if pairs < width {
+        f(
+            pairs,
+            mandel_iter(max_iter, frame.pixel(x + pairs as u32, y)),
+        );
+    }
+}
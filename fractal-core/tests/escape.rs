@@ -0,0 +1,109 @@
+use fractal_core::{
+    default_palette, mandel_color, mandel_iter, render_frame, Complex, Viewport, BOTTOM, RGB,
+};
+
+// Straight transcription of the contract, kept independent of mandel_orbit
+fn reference(max_iter: u64, c: Complex) -> Option<u64> {
+    let (mut re, mut im) = (c.re, c.im);
+    for n in 1..=max_iter {
+        if re * re + im * im > 4.0 {
+            return Some(n);
+        }
+        let next = re * re - im * im + c.re;
+        im = 2.0 * re * im + c.im;
+        re = next;
+    }
+    None
+}
+
+fn grid() -> Vec<Complex> {
+    let mut points = vec![];
+    for y in 0..=64 {
+        for x in 0..=96 {
+            points.push(Complex {
+                re: -2.5 + x as f64 * 3.5 / 96.0,
+                im: -1.25 + y as f64 * 2.5 / 64.0,
+            });
+        }
+    }
+    points
+}
+
+fn c(re: f64, im: f64) -> Complex {
+    Complex { re, im }
+}
+
+#[test]
+fn first_iteration_is_c() {
+    assert_eq!(mandel_iter(100, c(3.0, 0.0)), Some(1));
+    assert_eq!(mandel_iter(100, c(0.0, -2.5)), Some(1));
+    // 1, 2, 5
+    assert_eq!(mandel_iter(100, c(1.0, 0.0)), Some(3));
+}
+
+#[test]
+fn interior_is_none() {
+    assert_eq!(mandel_iter(100, c(0.0, 0.0)), None);
+    assert_eq!(mandel_iter(100, c(-1.0, 0.0)), None);
+    // |z| stays exactly 2, which doesn't escape
+    assert_eq!(mandel_iter(100, c(-2.0, 0.0)), None);
+    assert_eq!(mandel_iter(0, c(3.0, 0.0)), None);
+}
+
+#[test]
+fn escape_at_max_iter_counts() {
+    assert_eq!(mandel_iter(3, c(1.0, 0.0)), Some(3));
+    assert_eq!(mandel_iter(2, c(1.0, 0.0)), None);
+    assert_eq!(mandel_iter(1, c(3.0, 0.0)), Some(1));
+}
+
+#[test]
+fn matches_reference_on_grid() {
+    for max_iter in &[1, 2, 10, 255] {
+        for point in grid() {
+            assert_eq!(
+                mandel_iter(*max_iter, point),
+                reference(*max_iter, point),
+                "max_iter {} at {:?}",
+                max_iter,
+                point
+            );
+        }
+    }
+}
+
+#[test]
+fn frame_colours_follow_escape_count() {
+    let (width, height, max_iter) = (48, 32, 64);
+    // The default palette starts at black, so BOTTOM can't be told apart
+    let palette: Vec<RGB> = default_palette()
+        .into_iter()
+        .filter(|colour| *colour != BOTTOM)
+        .collect();
+    let frame = Viewport {
+        center_re: -0.5,
+        center_im: 0.0,
+        width: 3.0,
+    }
+    .frame(width, height);
+
+    let mut buf = vec![RGB::rgb(1, 2, 3); (width * height) as usize];
+    render_frame(&mut buf, width, height, &frame, max_iter, &palette);
+
+    for y in 0..height {
+        for x in 0..width {
+            let escape = reference(max_iter, frame.pixel(x, y));
+            let colour = buf[(y * width + x) as usize];
+            assert_eq!(colour, mandel_color(escape, &palette));
+            assert_eq!(colour == BOTTOM, escape.is_none());
+        }
+    }
+}
+
+#[test]
+fn empty_palette_colours_everything_bottom() {
+    assert_eq!(mandel_color(Some(7), &[]), BOTTOM);
+    assert_eq!(mandel_color(None, &[]), BOTTOM);
+    let palette = [RGB::rgb(1, 2, 3), RGB::rgb(4, 5, 6)];
+    assert_eq!(mandel_color(Some(3), &palette), palette[1]);
+}
@@ -0,0 +1,115 @@
+use fractal_core::{
+    colour_iterations, default_palette, escape_to_raw, iterate_rect, mandel_iter, raw_to_escape,
+    render_frame, Progressive, Rect, Viewport, BOTTOM, INTERIOR,
+};
+
+const MAX_ITER: u64 = 60;
+
+fn whole(width: u32, height: u32) -> Rect {
+    Rect {
+        x: 0,
+        y: 0,
+        width,
+        height,
+    }
+}
+
+#[test]
+fn raw_counts_round_trip() {
+    assert_eq!(escape_to_raw(None), INTERIOR);
+    assert_eq!(raw_to_escape(INTERIOR), None);
+    for n in &[1, 2, 255, 5000] {
+        assert_eq!(raw_to_escape(escape_to_raw(Some(*n))), Some(*n));
+    }
+    assert_eq!(escape_to_raw(Some(u64::MAX)), u32::MAX);
+}
+
+#[test]
+fn recolouring_matches_render() {
+    let (width, height) = (45, 31);
+    let frame = Viewport::DEFAULT.frame(width, height);
+    let size = (width * height) as usize;
+
+    let mut iters = vec![INTERIOR; size];
+    iterate_rect(
+        &mut iters,
+        0,
+        width as usize,
+        &frame,
+        whole(width, height),
+        MAX_ITER,
+    );
+    for y in 0..height {
+        for x in 0..width {
+            let raw = iters[(y * width + x) as usize];
+            assert_eq!(raw_to_escape(raw), mandel_iter(MAX_ITER, frame.pixel(x, y)));
+        }
+    }
+
+    // Any palette, without iterating again
+    let palettes = [
+        default_palette(),
+        vec![BOTTOM],
+        default_palette()[3..].to_vec(),
+    ];
+    for palette in &palettes {
+        let mut expected = vec![BOTTOM; size];
+        render_frame(&mut expected, width, height, &frame, MAX_ITER, palette);
+        let mut buf = vec![BOTTOM; size];
+        colour_iterations(&iters, &mut buf, palette);
+        assert!(buf == expected);
+    }
+}
+
+#[test]
+fn progressive_fills_iterations() {
+    let (width, height) = (50, 20);
+    let frame = Viewport::DEFAULT.frame(width, height);
+    let size = (width * height) as usize;
+    let palette = default_palette();
+
+    let mut expected = vec![INTERIOR; size];
+    iterate_rect(
+        &mut expected,
+        0,
+        width as usize,
+        &frame,
+        whole(width, height),
+        MAX_ITER,
+    );
+
+    let mut buf = vec![BOTTOM; size];
+    let mut iters = vec![INTERIOR; size];
+    let mut render = Progressive::new(frame, width, height, MAX_ITER, true);
+    while !render.step_with_iterations(&mut buf, &mut iters, &palette, 97) {}
+    assert_eq!(iters, expected);
+
+    let mut colours = vec![BOTTOM; size];
+    colour_iterations(&iters, &mut colours, &palette);
+    assert!(buf == colours);
+}
+
+// Colour cycling rotates the palette, which has to be the same as shifting
+// every escape count
+#[test]
+fn rotated_palettes_shift_counts() {
+    let palette = default_palette();
+    let iters: Vec<u32> = (0..100).collect();
+    for offset in 0..palette.len() * 2 {
+        let mut rotated = palette.clone();
+        rotated.rotate_left(offset % palette.len());
+        let mut buf = vec![BOTTOM; iters.len()];
+        colour_iterations(&iters, &mut buf, &rotated);
+
+        let shifted: Vec<u32> = iters
+            .iter()
+            .map(|&raw| match raw {
+                INTERIOR => INTERIOR,
+                n => n + offset as u32,
+            })
+            .collect();
+        let mut expected = vec![BOTTOM; iters.len()];
+        colour_iterations(&shifted, &mut expected, &palette);
+        assert!(buf == expected, "offset {}", offset);
+    }
+}
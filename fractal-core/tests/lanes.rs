@@ -0,0 +1,65 @@
+use fractal_core::{mandel_iter, mandel_iter_x2, Complex, F64x2, Lanes, Portable};
+
+fn grid() -> Vec<Complex> {
+    let mut points = vec![];
+    for y in 0..=80 {
+        for x in 0..=120 {
+            points.push(Complex {
+                re: -2.5 + x as f64 * 3.5 / 120.0,
+                im: -1.25 + y as f64 * 2.5 / 80.0,
+            });
+        }
+    }
+    points
+}
+
+fn odd_points() -> Vec<Complex> {
+    let c = |re, im| Complex { re, im };
+    vec![
+        c(0.0, 0.0),
+        c(-2.0, 0.0),
+        c(1.0, 0.0),
+        c(3.0, 0.0),
+        c(f64::INFINITY, 0.0),
+        c(f64::NAN, 0.0),
+        c(0.25, 0.0),
+        c(-0.75, 0.1),
+        c(-0.743_643_887_037_151, 0.131_825_904_205_33),
+    ]
+}
+
+fn check<V: F64x2>(points: &[Complex]) {
+    for &max_iter in &[0, 1, 2, 3, 17, 200] {
+        // Pair every point with its neighbour and with itself, so lanes
+        // escape both together and apart
+        for pair in points.windows(2).chain(points.chunks(1)) {
+            let c = [pair[0], pair[pair.len() - 1]];
+            let lanes = mandel_iter_x2::<V>(max_iter, c);
+            let scalar = [mandel_iter(max_iter, c[0]), mandel_iter(max_iter, c[1])];
+            assert_eq!(lanes, scalar, "max_iter {} at {:?}", max_iter, c);
+        }
+    }
+}
+
+#[test]
+fn portable_matches_scalar() {
+    check::<Portable>(&grid());
+    check::<Portable>(&odd_points());
+}
+
+#[test]
+fn native_lanes_match_scalar() {
+    check::<Lanes>(&grid());
+    check::<Lanes>(&odd_points());
+}
+
+#[test]
+fn masks_follow_lanes() {
+    let a = Lanes::new(1.0, 5.0);
+    let b = Lanes::splat(4.0);
+    assert_eq!(a.le(b), 0b01);
+    assert_eq!(a.gt(b), 0b10);
+    let nan = Lanes::new(f64::NAN, 4.0);
+    assert_eq!(nan.le(b), 0b10);
+    assert_eq!(nan.gt(b), 0b00);
+}
@@ -0,0 +1,82 @@
+use fractal_core::{
+    default_palette, render_frame, render_rect, shift_pixels, Complex, Frame, BOTTOM, RGB,
+};
+
+const MAX_ITER: u64 = 80;
+
+// Exactly representable, so panning by whole pixels lands on the same grid
+fn frame(x: i32, y: i32) -> Frame {
+    let step = 1.0 / 32.0;
+    Frame {
+        start: Complex {
+            re: -2.25 + x as f64 * step,
+            im: -1.25 + y as f64 * step,
+        },
+        step,
+    }
+}
+
+fn render(width: u32, height: u32, frame: &Frame) -> Vec<RGB> {
+    let mut buf = vec![BOTTOM; (width * height) as usize];
+    render_frame(&mut buf, width, height, frame, MAX_ITER, &default_palette());
+    buf
+}
+
+#[test]
+fn shifted_frame_matches_full_render() {
+    let (width, height) = (57, 41);
+    let palette = default_palette();
+    for &(dx, dy) in &[(5, 0), (0, -7), (-3, 4), (12, 9), (-56, -40), (0, 0)] {
+        let mut buf = render(width, height, &frame(0, 0));
+        // Content moving right by dx means the view moved left
+        let panned = frame(-dx, -dy);
+        for rect in shift_pixels(&mut buf, width, height, dx, dy).iter() {
+            let offset = (rect.y * width + rect.x) as usize;
+            render_rect(
+                &mut buf,
+                offset,
+                width as usize,
+                &panned,
+                *rect,
+                MAX_ITER,
+                &palette,
+            );
+        }
+        assert!(
+            buf == render(width, height, &panned),
+            "shift by ({}, {})",
+            dx,
+            dy
+        );
+    }
+}
+
+#[test]
+fn strips_cover_exposed_pixels_once() {
+    let (width, height) = (20u32, 15u32);
+    for &(dx, dy) in &[(3, 2), (-3, 2), (3, -2), (-19, 0), (0, 14), (25, 1)] {
+        let mut seen = vec![0u8; (width * height) as usize];
+        for rect in shift_pixels(&mut vec![0u8; seen.len()], width, height, dx, dy).iter() {
+            for y in rect.y..rect.y + rect.height {
+                for x in rect.x..rect.x + rect.width {
+                    seen[(y * width + x) as usize] += 1;
+                }
+            }
+        }
+        let exposed = seen.iter().filter(|n| **n == 1).count() as u32;
+        let kept =
+            width.saturating_sub(dx.unsigned_abs()) * height.saturating_sub(dy.unsigned_abs());
+        assert!(seen.iter().all(|n| *n <= 1));
+        assert_eq!(exposed, width * height - kept);
+    }
+}
+
+#[test]
+fn shift_moves_contents() {
+    let (width, height) = (4u32, 3u32);
+    let mut buf: Vec<u32> = (0..width * height).collect();
+    shift_pixels(&mut buf, width, height, 1, -1);
+    // Row 1 moves up to row 0 and one column right, row 2 to row 1
+    assert_eq!(&buf[1..4], &[4, 5, 6]);
+    assert_eq!(&buf[5..8], &[8, 9, 10]);
+}
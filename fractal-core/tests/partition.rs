@@ -0,0 +1,88 @@
+use fractal_core::{default_palette, render_frame, render_rect, Rect, Viewport, BOTTOM, RGB};
+
+const MAX_ITER: u64 = 50;
+
+#[test]
+fn bands_compose_the_frame() {
+    let (width, height) = (61, 47);
+    let palette = default_palette();
+    let frame = Viewport::DEFAULT.frame(width, height);
+
+    let mut expected = vec![BOTTOM; (width * height) as usize];
+    render_frame(&mut expected, width, height, &frame, MAX_ITER, &palette);
+
+    // Each band in its own buffer, as a worker would
+    for bands in 1..6 {
+        let mut composed = vec![];
+        let rows = height.div_ceil(bands);
+        for y in (0..height).step_by(rows as usize) {
+            let rect = Rect {
+                x: 0,
+                y,
+                width,
+                height: rows.min(height - y),
+            };
+            let mut band = vec![BOTTOM; (rect.width * rect.height) as usize];
+            render_rect(
+                &mut band,
+                0,
+                width as usize,
+                &frame,
+                rect,
+                MAX_ITER,
+                &palette,
+            );
+            composed.extend(band);
+        }
+        assert!(composed == expected, "{} bands", bands);
+    }
+}
+
+#[test]
+fn rects_land_at_offset_and_stride() {
+    let (width, height) = (40, 30);
+    let palette = default_palette();
+    let frame = Viewport::DEFAULT.frame(width, height);
+
+    let mut expected = vec![BOTTOM; (width * height) as usize];
+    render_frame(&mut expected, width, height, &frame, MAX_ITER, &palette);
+
+    // Quadrants written straight into one shared buffer
+    let mut shared = vec![RGB::rgb(1, 2, 3); (width * height) as usize];
+    for &(x, y) in &[(0, 0), (20, 0), (0, 15), (20, 15)] {
+        let rect = Rect {
+            x,
+            y,
+            width: 20,
+            height: 15,
+        };
+        let offset = (y * width + x) as usize;
+        assert!(rect.fits(shared.len(), offset, width as usize));
+        render_rect(
+            &mut shared,
+            offset,
+            width as usize,
+            &frame,
+            rect,
+            MAX_ITER,
+            &palette,
+        );
+    }
+    assert!(shared == expected);
+}
+
+#[test]
+fn fits_checks_the_last_row() {
+    let rect = Rect {
+        x: 0,
+        y: 0,
+        width: 10,
+        height: 3,
+    };
+    assert!(rect.fits(30, 0, 10));
+    assert!(rect.fits(35, 5, 10));
+    assert!(!rect.fits(35, 6, 10));
+    assert!(!rect.fits(100, 0, 9));
+    let empty = Rect { width: 0, ..rect };
+    assert!(empty.fits(0, 0, 0));
+}
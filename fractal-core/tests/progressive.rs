@@ -0,0 +1,58 @@
+use fractal_core::{default_palette, render_frame, Progressive, Viewport, BOTTOM, RGB};
+
+fn check(width: u32, height: u32, coarse: bool, budget: u32) {
+    let palette = default_palette();
+    let frame = Viewport::DEFAULT.frame(width, height);
+    let size = (width * height) as usize;
+
+    let mut expected = vec![BOTTOM; size];
+    render_frame(&mut expected, width, height, &frame, 50, &palette);
+
+    let mut buf = vec![RGB::rgb(1, 2, 3); size];
+    let mut render = Progressive::new(frame, width, height, 50, coarse);
+    let mut steps = 0;
+    let mut last = 0.0;
+    while !render.step(&mut buf, &palette, budget) {
+        assert!(render.progress() > last);
+        last = render.progress();
+        steps += 1;
+    }
+    assert_eq!(render.progress(), 1.0);
+    // Each pixel is sampled once, so the budget splits the work evenly
+    assert_eq!(steps, (size as u32 - 1) / budget);
+    assert!(buf == expected, "{}x{} coarse {}", width, height, coarse);
+}
+
+#[test]
+fn matches_full_render() {
+    for &(width, height) in &[(64, 48), (37, 23), (1, 1), (5, 40)] {
+        check(width, height, false, 100);
+        check(width, height, true, 100);
+        check(width, height, true, 7);
+    }
+}
+
+#[test]
+fn coarse_preview_fills_the_frame() {
+    let (width, height) = (64, 48);
+    let palette: Vec<RGB> = default_palette()
+        .into_iter()
+        .filter(|colour| *colour != BOTTOM)
+        .collect();
+    let mut buf = vec![RGB::rgb(1, 2, 3); (width * height) as usize];
+    let frame = Viewport::DEFAULT.frame(width, height);
+    let mut render = Progressive::new(frame, width, height, 50, true);
+
+    // One sample per 16x16 block covers every pixel
+    render.step(&mut buf, &palette, 4 * 3);
+    assert!(buf.iter().all(|pixel| *pixel != RGB::rgb(1, 2, 3)));
+    assert!(!render.finished());
+}
+
+#[test]
+fn empty_frames_are_finished() {
+    let frame = Viewport::DEFAULT.frame(1, 1);
+    let render = Progressive::new(frame, 0, 10, 50, true);
+    assert!(render.finished());
+    assert_eq!(render.progress(), 1.0);
+}
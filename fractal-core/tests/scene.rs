@@ -0,0 +1,74 @@
+use fractal_core::{default_palette, Colouring, Formula, Scene, RGB};
+
+fn deep() -> Scene {
+    Scene {
+        formula: Formula::Mandelbrot,
+        // More digits than an f64 holds
+        center_re: "-0.74364388703715870475219150611477".to_string(),
+        center_im: "0.13182590420531197049313205638965".to_string(),
+        zoom: "40.25".to_string(),
+        max_iter: 20000,
+        width: 1920,
+        height: 1080,
+        supersample: 3,
+        colouring: Colouring::Bands,
+        palette: default_palette(),
+    }
+}
+
+#[test]
+fn scene_round_trips() {
+    let scene = deep();
+    let text = scene.to_string();
+    assert_eq!(text.parse::<Scene>(), Ok(scene));
+    assert!(text.contains("center -0.74364388703715870475219150611477 "));
+}
+
+#[test]
+fn optional_keys_default() {
+    let text = "# hand written\n\
+                formula mandelbrot\n\
+                center -0.5 0\n\
+                zoom 0\n\
+                max_iter 100\n\
+                size 64 48\n\
+                \n\
+                palette ffffff FF0000\n";
+    let scene: Scene = text.parse().unwrap();
+    assert_eq!(scene.supersample, 1);
+    assert_eq!(scene.colouring, Colouring::Smooth);
+    assert_eq!(
+        scene.palette,
+        vec![RGB::rgb(255, 255, 255), RGB::rgb(255, 0, 0)]
+    );
+    assert_eq!((scene.width, scene.height), (64, 48));
+}
+
+#[test]
+fn bad_scenes_are_rejected() {
+    let good = deep().to_string();
+    let with = |key: &str, line: &str| {
+        good.lines()
+            .map(|l| if l.starts_with(key) { line } else { l })
+            .collect::<Vec<&str>>()
+            .join("\n")
+            .parse::<Scene>()
+    };
+    assert!(with("formula", "formula julia").is_err());
+    assert!(with("formula", "formula mandelbrot 2").is_err());
+    assert!(with("center", "center 1").is_err());
+    assert!(with("center", "center 1 nan").is_err());
+    assert!(with("zoom", "zoom deep").is_err());
+    // Past what an f64 holds
+    assert!(with("center", "center 1e400 0").is_err());
+    assert!(with("size", "size 0 10").is_err());
+    assert!(with("supersample", "supersample 0").is_err());
+    assert!(with("colouring", "colouring rainbow").is_err());
+    assert!(with("palette", "palette").is_err());
+    assert!(with("palette", "palette 12345").is_err());
+    assert!(with("max_iter", "iterations 5").is_err());
+    assert!(with("zoom", "# zoom removed").is_err());
+
+    let err = with("palette", "palette fffff g").unwrap_err();
+    assert!(err.starts_with("line 8:"), "{}", err);
+}
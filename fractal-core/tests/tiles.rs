@@ -0,0 +1,142 @@
+use fractal_core::{
+    default_palette, render_frame, Complex, Frame, TileCache, TileKey, Viewport, BOTTOM, RGB,
+};
+
+const MAX_ITER: u64 = 60;
+
+// On the texel grid of zoom 2 tiles of 16 texels
+fn aligned() -> Frame {
+    Frame {
+        start: Complex {
+            re: -2.0,
+            im: -1.25,
+        },
+        step: 1.0 / 64.0,
+    }
+}
+
+fn key(x: i64, y: i64) -> TileKey {
+    TileKey { x, y, zoom: 0 }
+}
+
+#[test]
+fn levels_keep_texels_within_pixels() {
+    let cache = TileCache::new(16, MAX_ITER, 8);
+    assert_eq!(cache.level(&aligned()), 2);
+    for width in &[3.0, 0.5, 1e-3, 1e-9] {
+        let frame = Viewport {
+            center_re: -0.5,
+            center_im: 0.0,
+            width: *width,
+        }
+        .frame(640, 480);
+        let zoom = cache.level(&frame);
+        let texel = |zoom: u32| 1.0 / (1u64 << zoom) as f64 / 16.0;
+        assert!(texel(zoom) <= frame.step);
+        assert!(zoom == 0 || texel(zoom - 1) > frame.step);
+    }
+}
+
+#[test]
+fn aligned_composite_matches_render() {
+    let (width, height) = (50, 40);
+    let palette = default_palette();
+    let mut cache = TileCache::new(16, MAX_ITER, 64);
+    let mut tiled = vec![BOTTOM; (width * height) as usize];
+    let missing = cache.render(&mut tiled, width, height, &aligned(), &palette, 64);
+    assert_eq!(missing, 0);
+
+    let mut direct = vec![BOTTOM; tiled.len()];
+    render_frame(&mut direct, width, height, &aligned(), MAX_ITER, &palette);
+    assert!(tiled == direct);
+}
+
+#[test]
+fn budget_limits_new_tiles() {
+    let (width, height) = (50, 40);
+    let palette = default_palette();
+    let mut cache = TileCache::new(16, MAX_ITER, 64);
+    let mut buf = vec![
+        RGB {
+            r: 1,
+            g: 2,
+            b: 3,
+            a: 4
+        };
+        (width * height) as usize
+    ];
+    let visible = cache.visible(&aligned(), width, height).len();
+
+    // Nothing made yet shows as interior
+    assert_eq!(
+        cache.render(&mut buf, width, height, &aligned(), &palette, 0),
+        visible
+    );
+    assert!(buf.iter().all(|p| *p == BOTTOM));
+    assert_eq!(
+        cache.render(&mut buf, width, height, &aligned(), &palette, 2),
+        visible - 2
+    );
+    assert_eq!(cache.len(), 2);
+    assert_eq!(
+        cache.render(&mut buf, width, height, &aligned(), &palette, visible),
+        0
+    );
+
+    // Zooming in stands in coarser tiles until the finer ones are made
+    let zoomed = Frame {
+        start: aligned().start,
+        step: aligned().step / 4.0,
+    };
+    let missing = cache.render(&mut buf, width, height, &zoomed, &palette, 0);
+    assert!(missing > 0);
+    assert!(buf.iter().any(|p| *p != BOTTOM));
+}
+
+#[test]
+fn least_recently_used_tiles_go_first() {
+    let mut cache = TileCache::new(4, MAX_ITER, 2);
+    cache.generate(key(0, 0));
+    cache.generate(key(1, 0));
+    cache.generate(key(0, 0));
+    cache.generate(key(-1, 0));
+    assert!(cache.contains(&key(0, 0)));
+    assert!(!cache.contains(&key(1, 0)));
+    assert!(cache.contains(&key(-1, 0)));
+
+    cache.set_max_iter(MAX_ITER + 1);
+    assert!(cache.is_empty());
+}
+
+#[test]
+fn visible_tiles_outlast_capacity() {
+    let (width, height) = (50, 40);
+    let palette = default_palette();
+    let mut cache = TileCache::new(16, MAX_ITER, 4);
+    let mut buf = vec![BOTTOM; (width * height) as usize];
+    let visible = cache.visible(&aligned(), width, height).len();
+    assert!(visible > 4);
+
+    assert_eq!(
+        cache.render(&mut buf, width, height, &aligned(), &palette, usize::MAX),
+        0
+    );
+    assert_eq!(cache.len(), visible);
+    // Nothing is made again for the same frame
+    assert_eq!(
+        cache.render(&mut buf, width, height, &aligned(), &palette, 0),
+        0
+    );
+    let mut direct = vec![BOTTOM; buf.len()];
+    render_frame(&mut direct, width, height, &aligned(), MAX_ITER, &palette);
+    assert!(buf == direct);
+
+    // Moving on drops the old frame's tiles back down to capacity
+    let elsewhere = Frame {
+        start: Complex { re: 1.0, im: 1.0 },
+        step: aligned().step,
+    };
+    let mut small = vec![BOTTOM; 4];
+    cache.render(&mut small, 2, 2, &elsewhere, &palette, usize::MAX);
+    assert_eq!(cache.len(), 4);
+}
@@ -0,0 +1,42 @@
+use fractal_core::Viewport;
+
+#[test]
+fn deep_frames_keep_distinct_pixels() {
+    let frame = Viewport {
+        center_re: -0.743_643_887_037_151,
+        center_im: 0.131_825_904_205_33,
+        width: 1e-12,
+    }
+    .frame(100, 100);
+
+    assert!((frame.step - 1e-14).abs() < 1e-20);
+    for x in 1..100 {
+        assert!(frame.pixel(x, 0).re > frame.pixel(x - 1, 0).re);
+        assert!(frame.pixel(0, x).im > frame.pixel(0, x - 1).im);
+    }
+}
+
+#[test]
+fn pan_follows_the_drag() {
+    let mut view = Viewport::DEFAULT;
+    let before = view.frame(300, 200).pixel(100, 50);
+    view.pan(300, 20.0, -10.0);
+    let after = view.frame(300, 200).pixel(120, 40);
+    assert!((before.re - after.re).abs() < 1e-12);
+    assert!((before.im - after.im).abs() < 1e-12);
+}
+
+#[test]
+fn zoom_keeps_the_point_under_the_cursor() {
+    let mut view = Viewport::DEFAULT;
+    let before = view.frame(300, 200).pixel(40, 150);
+    view.zoom_at(300, 200, 40.0, 150.0, 8.0);
+    assert_eq!(view.width, 3.0 / 8.0);
+    let after = view.frame(300, 200).pixel(40, 150);
+    assert!((before.re - after.re).abs() < 1e-12);
+    assert!((before.im - after.im).abs() < 1e-12);
+
+    view.zoom_at(300, 200, 40.0, 150.0, 1.0 / 8.0);
+    assert!((view.center_re - Viewport::DEFAULT.center_re).abs() < 1e-12);
+    assert!((view.center_im - Viewport::DEFAULT.center_im).abs() < 1e-12);
+}
@@ -0,0 +1,173 @@
+#[macro_use]
+extern crate criterion;
+extern crate fractal_core;
+extern crate fractal_rs;
+extern crate num;
+extern crate rgb;
+
+use criterion::{BenchmarkId, Criterion, Throughput};
+use fractal_core::Complex;
+use fractal_rs::simd::SimdGenerator;
+use fractal_rs::*;
+use num::complex::Complex64;
+use rgb::RGB8;
+use std::collections::HashMap;
+use std::hint::black_box;
+
+// cargo bench -p fractal-rs, add -- <filter> for a single group
+
+const MAX_ITER: u64 = 1000;
+
+// A grid over the whole set, mixing fast escapes and interior points
+fn points(n: usize) -> Vec<Complex> {
+    let mut points = Vec::with_capacity(n * n);
+    for y in 0..n {
+        for x in 0..n {
+            points.push(Complex {
+                re: -2.0 + 2.5 * x as f64 / n as f64,
+                im: -1.25 + 2.5 * y as f64 / n as f64,
+            });
+        }
+    }
+    points
+}
+
+fn generator(size: usize) -> GeneratorConfig {
+    GeneratorConfig {
+        size: Point { x: size, y: size },
+        max_iter: MAX_ITER,
+    }
+}
+
+// Renderer that has already cached the tiles viewport needs at size
+fn warm_renderer(viewport: &ViewportConfig, size: usize, filter: SampleFilter) -> RenderConfig {
+    let mut renderer = RenderConfig {
+        manager: Box::new(TileStorage {
+            generator: Box::new(SimdGenerator::new(generator(60))),
+            storage: HashMap::new(),
+            filter,
+        }),
+        palette: vec![
+            RGB8 {
+                r: 255,
+                g: 255,
+                b: 255,
+            },
+            RGB8 { r: 255, g: 0, b: 0 },
+        ],
+        bottom: RGB8 { r: 0, g: 0, b: 0 },
+        size: Point { x: size, y: size },
+        tile_width: 3.0,
+        blend_levels: true,
+    };
+    renderer.render(viewport);
+    renderer
+}
+
+fn full_set() -> ViewportConfig {
+    ViewportConfig {
+        center: ComplexSpace(Complex64 { re: -0.5, im: 0.0 }),
+        zoom: 0.5,
+    }
+}
+
+fn mandel_iter_throughput(c: &mut Criterion) {
+    let points = points(64);
+    let mut group = c.benchmark_group("mandel_iter");
+    group.throughput(Throughput::Elements(points.len() as u64));
+    group.bench_function("escape count", |b| {
+        b.iter(|| {
+            for p in &points {
+                black_box(mandel_iter(MAX_ITER, *p));
+            }
+        })
+    });
+    group.bench_function("smooth", |b| {
+        b.iter(|| {
+            for p in &points {
+                black_box(mandel_smooth(MAX_ITER, Complex64 { re: p.re, im: p.im }));
+            }
+        })
+    });
+    group.finish();
+}
+
+fn tile_generation(c: &mut Criterion) {
+    // The renderer's tile size
+    let size = 60;
+    // On the edge of the set, so texels take very different times
+    let tile = TileSpace {
+        index: Point { x: -2, y: -1 },
+        zoom: 1,
+    };
+    let mut group = c.benchmark_group("generate");
+    group.throughput(Throughput::Elements((size * size) as u64));
+    let generators: Vec<(&str, Box<dyn Generator>)> = vec![
+        ("scalar", Box::new(generator(size))),
+        ("simd", Box::new(SimdGenerator::new(generator(size)))),
+    ];
+    for (name, generator) in generators.iter() {
+        group.bench_function(*name, |b| b.iter(|| black_box(generator.generate(&tile))));
+    }
+    group.finish();
+}
+
+// TileStorage::sample once every tile it reads is cached
+fn cached_samples(c: &mut Criterion) {
+    let locations: Vec<Complex64> = points(32)
+        .iter()
+        .map(|p| Complex64 {
+            re: p.re * 0.5,
+            im: p.im * 0.5,
+        })
+        .collect();
+    let mut group = c.benchmark_group("sample");
+    group.throughput(Throughput::Elements(locations.len() as u64));
+    for filter in [
+        SampleFilter::Nearest,
+        SampleFilter::Bilinear,
+        SampleFilter::Bicubic,
+    ]
+    .iter()
+    {
+        let mut storage = TileStorage {
+            generator: Box::new(SimdGenerator::new(generator(60))),
+            storage: HashMap::new(),
+            filter: *filter,
+        };
+        for l in &locations {
+            storage.sample(ComplexSpace(*l), 3);
+        }
+        group.bench_function(BenchmarkId::from_parameter(format!("{:?}", filter)), |b| {
+            b.iter(|| {
+                for l in &locations {
+                    black_box(storage.sample(ComplexSpace(*l), 3));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn render(c: &mut Criterion) {
+    let size = 256;
+    let viewport = full_set();
+    let mut group = c.benchmark_group("render");
+    group.throughput(Throughput::Elements((size * size) as u64));
+    for filter in [SampleFilter::Nearest, SampleFilter::Bilinear].iter() {
+        let mut renderer = warm_renderer(&viewport, size, *filter);
+        group.bench_function(BenchmarkId::from_parameter(format!("{:?}", filter)), |b| {
+            b.iter(|| black_box(renderer.render(&viewport)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    mandel_iter_throughput,
+    tile_generation,
+    cached_samples,
+    render
+);
+criterion_main!(benches);
@@ -0,0 +1,6 @@
+extern crate fractal_rs;
+
+// fractal-rs worker, see fractal_rs::worker
+fn main() {
+    fractal_rs::worker::run()
+}
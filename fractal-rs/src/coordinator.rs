@@ -0,0 +1,447 @@
+// Distributed render coordinator
+//
+// Splits a viewport into the TileSpace tiles it samples and hands them out to
+// workers that pull jobs over the wire protocol in protocol.rs. Jobs that
+// aren't returned in time go back on the queue for the next worker that asks,
+// and every result is spot checked by recomputing a few texels locally.
+//
+// Coordinator itself knows nothing about sockets. A Session handles one
+// worker's messages, and `run` drives a session per websocket connection.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use fractal_core::Colouring;
+use tungstenite::accept;
+
+use protocol::{Capabilities, JobSpec, Message, Transport, VERSION};
+use scene;
+use {
+    arg, arg_or, generator, Generator, GeneratorConfig, PixelRenderer, Point, RenderConfig,
+    SampleFilter, Tile, TileHash, TileSpace, ViewportConfig, MAX_ITER,
+};
+
+pub const FORMULA: &str = "mandelbrot";
+
+// How long a worker waits before asking again when the queue is empty
+const RETRY_MS: u32 = 250;
+
+pub type WorkerId = u64;
+pub type JobId = u64;
+
+#[derive(Debug, Clone)]
+enum JobState {
+    Queued,
+    Assigned { worker: WorkerId, since: Instant },
+    Done,
+}
+
+struct Job {
+    tile: TileSpace,
+    state: JobState,
+}
+
+pub struct Coordinator {
+    generator: GeneratorConfig,
+    jobs: Vec<Job>, // indexed by JobId
+    queue: VecDeque<JobId>,
+    results: HashMap<TileHash, Tile>,
+    workers: HashMap<WorkerId, Capabilities>,
+    next_worker: WorkerId,
+    timeout: Duration,
+    spot_checks: usize,
+}
+
+// Tiles the renderer will sample for this viewport, including a margin for
+// the filter footprint at the edges
+pub fn plan(renderer: &RenderConfig, viewport: &ViewportConfig) -> Vec<TileSpace> {
+    let (start, step) = renderer.view(viewport);
+    let (level, blend) = renderer.levels(step);
+    let levels = if blend > 0.0 {
+        vec![level, level + 1]
+    } else {
+        vec![level]
+    };
+    let tile_size = renderer.manager.tile_size().clone();
+
+    let mut tiles = vec![];
+    for zoom in levels {
+        let tile_w = 1.0 / 2.0_f64.powi(zoom as i32);
+        let margin = 2.0 * tile_w / tile_size.x.min(tile_size.y) as f64;
+        let index = |v: f64| (v / tile_w).floor() as i64;
+
+        let (x0, x1) = (
+            index(start.x - margin),
+            index(start.x + renderer.size.x as f64 * step + margin),
+        );
+        let (y0, y1) = (
+            index(start.y - margin),
+            index(start.y + renderer.size.y as f64 * step + margin),
+        );
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                tiles.push(TileSpace {
+                    index: Point { x, y },
+                    zoom,
+                });
+            }
+        }
+    }
+    tiles
+}
+
+// xorshift64*, enough to pick texels without pulling in rand
+fn scatter(seed: u64) -> impl Iterator<Item = u64> {
+    let mut state = seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1;
+    (0..).map(move |_| {
+        state ^= state >> 12;
+        state ^= state << 25;
+        state ^= state >> 27;
+        state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    })
+}
+
+impl Coordinator {
+    pub fn new(generator: GeneratorConfig, tiles: Vec<TileSpace>, timeout: Duration) -> Self {
+        Coordinator {
+            generator,
+            queue: (0..tiles.len() as JobId).collect(),
+            jobs: tiles
+                .into_iter()
+                .map(|tile| Job {
+                    tile,
+                    state: JobState::Queued,
+                })
+                .collect(),
+            results: HashMap::new(),
+            workers: HashMap::new(),
+            next_worker: 0,
+            timeout,
+            spot_checks: 8,
+        }
+    }
+
+    pub fn connect(&mut self, caps: Capabilities) -> Result<WorkerId, String> {
+        if caps.version != VERSION {
+            return Err(format!("protocol version {} != {}", caps.version, VERSION));
+        }
+        if !caps.formulas.iter().any(|f| f == FORMULA) {
+            return Err(format!("{} is required", FORMULA));
+        }
+        if caps.precision < 64 {
+            return Err("f64 precision is required".to_string());
+        }
+        let id = self.next_worker;
+        self.next_worker += 1;
+        self.workers.insert(id, caps);
+        Ok(id)
+    }
+
+    // Anything the worker was still working on goes back on the queue
+    pub fn disconnect(&mut self, worker: WorkerId) {
+        self.workers.remove(&worker);
+        for (id, job) in self.jobs.iter_mut().enumerate() {
+            if let JobState::Assigned { worker: w, .. } = job.state {
+                if w == worker {
+                    job.state = JobState::Queued;
+                    self.queue.push_back(id as JobId);
+                }
+            }
+        }
+    }
+
+    // Requeue jobs that have been out for longer than the timeout
+    pub fn reap(&mut self, now: Instant) {
+        for (id, job) in self.jobs.iter_mut().enumerate() {
+            if let JobState::Assigned { since, .. } = job.state {
+                if now.duration_since(since) > self.timeout {
+                    job.state = JobState::Queued;
+                    self.queue.push_back(id as JobId);
+                }
+            }
+        }
+    }
+
+    pub fn request(&mut self, worker: WorkerId, count: u16, now: Instant) -> Vec<JobSpec> {
+        self.reap(now);
+
+        let mut jobs = vec![];
+        while jobs.len() < count as usize {
+            let id = match self.queue.pop_front() {
+                Some(id) => id,
+                None => break,
+            };
+            let job = &mut self.jobs[id as usize];
+            // A reaped job may have come back from its original worker since
+            if let JobState::Queued = job.state {
+                job.state = JobState::Assigned { worker, since: now };
+                jobs.push(JobSpec {
+                    id,
+                    tile: job.tile.clone(),
+                    size: self.generator.size.clone(),
+                    max_iter: self.generator.max_iter,
+                    formula: FORMULA.to_string(),
+                });
+            }
+        }
+        jobs
+    }
+
+    // Late results for reassigned jobs are still welcome, the first valid
+    // result for a job wins. An Err means the worker can't be trusted.
+    pub fn submit(&mut self, job: JobId, data: Vec<Option<f32>>) -> Result<(), String> {
+        let tile = match self.jobs.get(job as usize) {
+            Some(Job {
+                state: JobState::Done,
+                ..
+            }) => return Ok(()),
+            Some(j) => j.tile.clone(),
+            None => return Err(format!("unknown job {}", job)),
+        };
+        let size = &self.generator.size;
+        if data.len() != size.x * size.y {
+            return Err(format!("job {} has {} texels", job, data.len()));
+        }
+
+        for i in scatter(job).take(self.spot_checks) {
+            let (x, y) = (
+                (i % size.x as u64) as usize,
+                (i / size.x as u64) as usize % size.y,
+            );
+            let expected = self.generator.generate_texel(&tile, x, y);
+            let got = data[y * size.x + x];
+            let matches = match (expected, got) {
+                (None, None) => true,
+                // Allow for browser workers with a different smoothing path
+                (Some(e), Some(g)) => (e - g).abs() <= 1e-3 * e.abs().max(1.0),
+                _ => false,
+            };
+            if !matches {
+                return Err(format!(
+                    "job {} texel {},{} is {:?}, expected {:?}",
+                    job, x, y, got, expected
+                ));
+            }
+        }
+
+        self.results
+            .insert(self.generator.hash(&tile), Tile { data });
+        self.jobs[job as usize].state = JobState::Done;
+        Ok(())
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.jobs.len() - self.results.len()
+    }
+
+    pub fn take_results(&mut self) -> HashMap<TileHash, Tile> {
+        std::mem::take(&mut self.results)
+    }
+}
+
+// One worker's conversation with the coordinator, whatever the transport.
+// The worker's jobs are released when the session is dropped.
+pub struct Session<'a> {
+    coordinator: &'a Mutex<Coordinator>,
+    worker: Option<WorkerId>,
+    closed: bool,
+}
+
+impl<'a> Session<'a> {
+    pub fn new(coordinator: &'a Mutex<Coordinator>) -> Self {
+        Session {
+            coordinator,
+            worker: None,
+            closed: false,
+        }
+    }
+
+    // True once a Goodbye has been sent
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    fn goodbye(&mut self, reason: String) -> Vec<Message> {
+        self.closed = true;
+        vec![Message::Goodbye { reason }]
+    }
+
+    // Replies to one message from the worker
+    pub fn handle(&mut self, message: Message) -> Result<Vec<Message>, String> {
+        let worker = match (self.worker, message) {
+            (None, Message::Hello(caps)) => {
+                return match self.coordinator.lock().unwrap().connect(caps) {
+                    Ok(worker) => {
+                        self.worker = Some(worker);
+                        Ok(vec![])
+                    }
+                    Err(reason) => Ok(self.goodbye(reason)),
+                };
+            }
+            (None, m) => return Err(format!("expected hello, got {:?}", m)),
+            (Some(_), Message::Hello(_)) => return Err("repeated hello".to_string()),
+            (Some(worker), m) => (worker, m),
+        };
+
+        match worker {
+            (worker, Message::Request { count }) => {
+                let (jobs, remaining) = {
+                    let mut c = self.coordinator.lock().unwrap();
+                    (c.request(worker, count, Instant::now()), c.remaining())
+                };
+                if remaining == 0 {
+                    return Ok(self.goodbye("done".to_string()));
+                }
+                let short = jobs.len() < count as usize;
+                let mut replies: Vec<Message> = jobs.into_iter().map(Message::Job).collect();
+                // Lets the worker know not to wait for the rest
+                if short {
+                    replies.push(Message::Idle { retry_ms: RETRY_MS });
+                }
+                Ok(replies)
+            }
+            (worker, Message::Result { job, data }) => {
+                let submitted = self.coordinator.lock().unwrap().submit(job, data);
+                match submitted {
+                    Ok(()) => Ok(vec![]),
+                    Err(reason) => {
+                        println!("Dropping worker {}: {}", worker, reason);
+                        Ok(self.goodbye(reason))
+                    }
+                }
+            }
+            (_, m) => Err(format!("unexpected {:?}", m)),
+        }
+    }
+}
+
+impl<'a> Drop for Session<'a> {
+    fn drop(&mut self) {
+        if let Some(worker) = self.worker {
+            self.coordinator.lock().unwrap().disconnect(worker);
+        }
+    }
+}
+
+// In process stand-in for a websocket to the coordinator
+pub struct LocalCoordinator<'a> {
+    session: Session<'a>,
+    inbox: VecDeque<Message>,
+}
+
+impl<'a> LocalCoordinator<'a> {
+    pub fn new(coordinator: &'a Mutex<Coordinator>) -> Self {
+        LocalCoordinator {
+            session: Session::new(coordinator),
+            inbox: VecDeque::new(),
+        }
+    }
+}
+
+impl<'a> Transport for LocalCoordinator<'a> {
+    fn send(&mut self, message: &Message) -> Result<(), String> {
+        if self.session.is_closed() {
+            return Err("connection closed".to_string());
+        }
+        // Encoding keeps this honest about what fits on the wire
+        let message = Message::decode(&message.encode())?;
+        self.inbox.extend(self.session.handle(message)?);
+        Ok(())
+    }
+
+    fn receive(&mut self) -> Result<Option<Message>, String> {
+        Ok(self.inbox.pop_front())
+    }
+}
+
+fn serve_worker<T: Transport>(
+    socket: &mut T,
+    coordinator: &Mutex<Coordinator>,
+) -> Result<(), String> {
+    let mut session = Session::new(coordinator);
+    while !session.is_closed() {
+        let message = match socket.receive()? {
+            Some(message) => message,
+            None => break,
+        };
+        for reply in session.handle(message)? {
+            socket.send(&reply)?;
+        }
+    }
+    Ok(())
+}
+
+// fractal-rs coordinate --bind 127.0.0.1:9000 --timeout 30 --scene view.scene
+//
+// Renders the same view as plain fractal-rs, the default one or a smooth
+// scene file, with the tiles generated by workers.
+pub fn run() {
+    let bind = arg("bind").unwrap_or_else(|| "127.0.0.1:9000".to_string());
+    let timeout = Duration::from_secs(arg_or("timeout", 30));
+    let filter = arg_or("filter", SampleFilter::Bilinear);
+    let scene = match arg("scene") {
+        Some(path) => scene::load(&path).unwrap_or_else(|e| panic!("{}", e)),
+        None => scene::default_scene(arg_or("max-iter", MAX_ITER)),
+    };
+    if scene.colouring != Colouring::Smooth {
+        panic!("only smooth scenes are rendered from tiles");
+    }
+    let viewport = match scene::viewport(&scene) {
+        Ok(viewport) => viewport,
+        Err(e) => panic!("invalid scene: {}", e),
+    };
+
+    let tiles = plan(&scene::renderer(&scene, HashMap::new(), filter), &viewport);
+    println!("Coordinating {} tiles on ws://{}", tiles.len(), bind);
+
+    let coordinator = Coordinator::new(generator(scene.max_iter), tiles, timeout);
+    let coordinator = Arc::new(Mutex::new(coordinator));
+
+    let listener = match TcpListener::bind(&bind) {
+        Ok(listener) => listener,
+        Err(e) => panic!("failed to bind {}: {}", bind, e),
+    };
+    let accepting = Arc::clone(&coordinator);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let coordinator = Arc::clone(&accepting);
+            thread::spawn(move || {
+                let result = stream
+                    .map_err(|e| e.to_string())
+                    .and_then(|stream| accept(stream).map_err(|e| e.to_string()))
+                    .and_then(|mut socket| serve_worker(&mut socket, &coordinator));
+                if let Err(e) = result {
+                    println!("Worker failed: {}", e);
+                }
+            });
+        }
+    });
+
+    while coordinator.lock().unwrap().remaining() > 0 {
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    // Let workers still busy with requeued duplicates hear that we're done
+    let deadline = Instant::now() + timeout;
+    while Arc::strong_count(&coordinator) > 2 && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    let storage = coordinator.lock().unwrap().take_results();
+    let pixels = scene::renderer(&scene, storage, filter).render(&viewport);
+    let pixels = scene::downsample(&scene, &pixels);
+
+    if let Err(e) = lodepng::encode_file(
+        arg_or("out", "mandel.png".to_string()),
+        &pixels,
+        scene.width as usize,
+        scene.height as usize,
+        lodepng::ColorType::RGB,
+        8,
+    ) {
+        panic!("failed to write png: {:?}", e);
+    }
+}
@@ -0,0 +1,189 @@
+// XYZ ("slippy map") tile pyramid export
+//
+// Web map viewers address tiles as {z}/{x}/{y}.png, where zoom z splits a
+// square world into 2^z by 2^z tiles, x grows to the right and y grows
+// downwards. The world here is a square of complex space, so each zoom level
+// halves the tile width just like TileSpace does.
+
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use fractal_core::MAX_ZOOM;
+use num::complex::Complex64;
+use rgb::RGB8;
+
+use {
+    arg, arg_or, renderer, ComplexSpace, PixelRenderer, Point, SampleFilter, ViewportConfig,
+    MAX_ITER,
+};
+
+pub const TILE_PIXELS: usize = 256;
+
+// Top left corner and width of the world in complex space
+pub const WORLD_RE: f64 = -2.5;
+pub const WORLD_IM: f64 = 2.0;
+pub const WORLD_SIZE: f64 = 4.0;
+
+// Complex space rectangle, parsed from "re_min,im_min,re_max,im_max"
+#[derive(Debug, Clone)]
+struct Region {
+    min: Point<f64>,
+    max: Point<f64>,
+}
+
+impl Region {
+    fn world() -> Self {
+        Region {
+            min: Point {
+                x: WORLD_RE,
+                y: WORLD_IM - WORLD_SIZE,
+            },
+            max: Point {
+                x: WORLD_RE + WORLD_SIZE,
+                y: WORLD_IM,
+            },
+        }
+    }
+
+    // Range of tile indices at zoom z overlapping the region on one axis
+    fn span(from: f64, to: f64, z: u32) -> (u64, u64) {
+        let count = 1u64 << z;
+        let width = WORLD_SIZE / count as f64;
+        let first = (from / width).floor().max(0.0) as u64;
+        let last = ((to / width).ceil() as u64).min(count);
+        (first, last)
+    }
+
+    fn tiles(&self, z: u32) -> ((u64, u64), (u64, u64)) {
+        (
+            Region::span(self.min.x - WORLD_RE, self.max.x - WORLD_RE, z),
+            // y counts down from the top of the world
+            Region::span(WORLD_IM - self.max.y, WORLD_IM - self.min.y, z),
+        )
+    }
+}
+
+impl FromStr for Region {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let v: Vec<f64> = s
+            .split(',')
+            .map(|v| v.trim().parse().map_err(|e| format!("{}: {}", v, e)))
+            .collect::<Result<_, _>>()?;
+        if v.len() != 4 || v[0] >= v[2] || v[1] >= v[3] {
+            return Err(format!("expected re_min,im_min,re_max,im_max, got {:?}", s));
+        }
+        Ok(Region {
+            min: Point { x: v[0], y: v[1] },
+            max: Point { x: v[2], y: v[3] },
+        })
+    }
+}
+
+// Renders tile z/x/y with its top row at the highest imaginary value
+pub fn render_tile<R: PixelRenderer>(renderer: &mut R, z: u32, x: u64, y: u64) -> Vec<RGB8> {
+    let width = WORLD_SIZE / (1u64 << z) as f64;
+    let viewport = ViewportConfig {
+        center: ComplexSpace(Complex64 {
+            re: WORLD_RE + (x as f64 + 0.5) * width,
+            im: WORLD_IM - (y as f64 + 0.5) * width,
+        }),
+        zoom: z as f64,
+    };
+
+    // The renderer walks rows with increasing imaginary part
+    let pixels = renderer.render(&viewport);
+    pixels
+        .chunks(TILE_PIXELS)
+        .rev()
+        .flat_map(|row| row.iter().cloned())
+        .collect()
+}
+
+fn metadata(region: &Region, min_zoom: u32, max_zoom: u32, max_iter: u64) -> String {
+    // TileJSON, with bounds given in complex space rather than lon/lat
+    format!(
+        r#"{{
+  "tilejson": "2.2.0",
+  "name": "mandelbrot",
+  "scheme": "xyz",
+  "tiles": ["{{z}}/{{x}}/{{y}}.png"],
+  "tileSize": {},
+  "minzoom": {},
+  "maxzoom": {},
+  "bounds": [{}, {}, {}, {}],
+  "world": {{ "re": {}, "im": {}, "size": {} }},
+  "max_iter": {}
+}}
+"#,
+        TILE_PIXELS,
+        min_zoom,
+        max_zoom,
+        region.min.x,
+        region.min.y,
+        region.max.x,
+        region.max.y,
+        WORLD_RE,
+        WORLD_IM,
+        WORLD_SIZE,
+        max_iter
+    )
+}
+
+// fractal-rs export --out tiles --min-zoom 0 --max-zoom 4 --region -2,-1.5,1,1.5
+pub fn run() {
+    let out = arg("out").unwrap_or_else(|| "tiles".to_string());
+    let min_zoom: u32 = arg_or("min-zoom", 0);
+    let max_zoom: u32 = arg_or("max-zoom", 4);
+    let region: Region = arg_or("region", Region::world());
+    let max_iter = arg_or("max-iter", MAX_ITER);
+    let out = Path::new(&out);
+    if min_zoom > max_zoom || max_zoom > MAX_ZOOM {
+        panic!(
+            "invalid zooms {}..{}, expected --min-zoom <= --max-zoom <= {}",
+            min_zoom, max_zoom, MAX_ZOOM
+        );
+    }
+
+    let mut renderer = renderer(
+        Point {
+            x: TILE_PIXELS,
+            y: TILE_PIXELS,
+        },
+        WORLD_SIZE as f32,
+        max_iter,
+        arg_or("filter", SampleFilter::Bilinear),
+    );
+
+    for z in min_zoom..=max_zoom {
+        let ((x_from, x_to), (y_from, y_to)) = region.tiles(z);
+        for x in x_from..x_to {
+            let dir = out.join(z.to_string()).join(x.to_string());
+            if let Err(e) = fs::create_dir_all(&dir) {
+                panic!("failed to create {:?}: {}", dir, e);
+            }
+            for y in y_from..y_to {
+                let pixels = render_tile(&mut renderer, z, x, y);
+                if let Err(e) = lodepng::encode_file(
+                    dir.join(format!("{}.png", y)),
+                    &pixels,
+                    TILE_PIXELS,
+                    TILE_PIXELS,
+                    lodepng::ColorType::RGB,
+                    8,
+                ) {
+                    panic!("failed to write png: {:?}", e);
+                }
+            }
+        }
+        // Levels share few tiles, so drop this one's before the next
+        renderer.manager.clear();
+    }
+
+    let meta = metadata(&region, min_zoom, max_zoom, max_iter);
+    if let Err(e) = fs::write(out.join("metadata.json"), meta) {
+        panic!("failed to write metadata: {}", e);
+    }
+}
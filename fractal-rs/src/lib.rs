@@ -0,0 +1,551 @@
+extern crate fractal_core;
+extern crate num;
+extern crate rgb;
+extern crate tungstenite;
+
+use fractal_core::{mandel_orbit, Complex, MAX_ZOOM};
+use num::complex::Complex64;
+use num::pow::pow;
+use rgb::*;
+use simd::SimdGenerator;
+use std::collections::HashMap;
+use std::env;
+use std::fmt::Debug;
+use std::ops::Deref;
+use std::str::FromStr;
+
+pub mod coordinator;
+pub mod export;
+pub mod protocol;
+pub mod scene;
+pub mod server;
+pub mod simd;
+pub mod worker;
+
+pub use fractal_core::mandel_iter;
+
+pub const MAX_ITER: u64 = 5000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Point<T> {
+    pub x: T,
+    pub y: T,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileSpace {
+    // unique data per tile
+    pub index: Point<i64>, // big int
+    pub zoom: usize,       // big int
+}
+
+#[derive(Debug, Clone)]
+struct SampleSpace {
+    tile: TileSpace,
+    coord: Point<f32>, // u,v 0-1
+}
+
+impl SampleSpace {
+    fn from_complex(c: &ComplexSpace, z: usize) -> SampleSpace {
+        let zoom_power = 2f64.powi(z as i32);
+        let tile_x = zoom_power * c.re;
+        let tile_y = zoom_power * c.im;
+
+        let sample_x = (tile_x % 1.0) as f32;
+        let sample_x = if sample_x < 0.0 {
+            1.0 + sample_x
+        } else {
+            sample_x
+        };
+        let sample_y = (tile_y % 1.0) as f32;
+        let sample_y = if sample_y < 0.0 {
+            1.0 + sample_y
+        } else {
+            sample_y
+        };
+
+        SampleSpace {
+            tile: TileSpace {
+                index: Point {
+                    x: tile_x.floor() as i64,
+                    y: tile_y.floor() as i64,
+                },
+                zoom: z,
+            },
+            coord: Point {
+                x: sample_x,
+                y: sample_y,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ComplexSpace(pub Complex64); // big decimal
+impl ComplexSpace {
+    fn from(t: &TileSpace) -> Self {
+        return ComplexSpace(Complex64 {
+            re: t.index.x as f64 / pow(2.0, t.zoom),
+            im: t.index.y as f64 / pow(2.0, t.zoom),
+        });
+    }
+}
+impl Deref for ComplexSpace {
+    type Target = Complex64;
+
+    fn deref(&self) -> &Complex64 {
+        &self.0
+    }
+}
+
+// Generator
+pub trait Generator {
+    fn generate(&self, tile: &TileSpace) -> Tile;
+    fn generate_texel(&self, tile: &TileSpace, x: usize, y: usize) -> Option<f32>;
+    fn size(&self) -> &Point<usize>;
+    fn hash(&self, tile: &TileSpace) -> TileHash;
+    fn texel(&self, data: &Tile, x: usize, y: usize) -> Option<f32>;
+}
+
+pub struct GeneratorConfig {
+    pub size: Point<usize>,
+    pub max_iter: u64, // big integer?
+}
+
+pub struct Tile {
+    pub data: Vec<Option<f32>>, // smooth iteration count, None inside the set
+}
+
+// Tile Manager
+pub trait TileManager {
+    fn sample(&mut self, location: ComplexSpace, zoom: usize) -> Option<f32>;
+    fn tile_size(&self) -> &Point<usize>;
+    // Drops every generated tile, for long running renderers to bound memory
+    fn clear(&mut self);
+}
+pub type TileHash = String;
+
+pub struct TileStorage {
+    pub generator: Box<dyn Generator>,
+    pub storage: HashMap<TileHash, Tile>,
+    pub filter: SampleFilter,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SampleFilter {
+    Nearest,
+    Bilinear, // 2x2 texels
+    Bicubic,  // 4x4 texels, Catmull-Rom
+}
+
+impl FromStr for SampleFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nearest" => Ok(SampleFilter::Nearest),
+            "bilinear" => Ok(SampleFilter::Bilinear),
+            "bicubic" => Ok(SampleFilter::Bicubic),
+            _ => Err(format!("unknown filter {:?}", s)),
+        }
+    }
+}
+
+// Renderer
+pub trait PixelRenderer {
+    fn render(&mut self, viewport: &ViewportConfig) -> Vec<RGB8>;
+}
+
+pub struct RenderConfig {
+    pub manager: Box<dyn TileManager>,
+    pub palette: Vec<RGB8>,
+    pub bottom: RGB8,
+    pub size: Point<usize>,
+    pub tile_width: f32,
+    // Blend the two tile levels either side of the ideal level of detail,
+    // rather than snapping to the finer one
+    pub blend_levels: bool,
+}
+
+pub struct ViewportConfig {
+    pub center: ComplexSpace,
+    pub zoom: f64, // big decimal
+}
+
+impl GeneratorConfig {
+    // Complex space origin of the tile and the distance between texels
+    fn texel_space(&self, tile: &TileSpace) -> (ComplexSpace, Point<f64>) {
+        let z: usize = tile.zoom;
+        let start = ComplexSpace::from(tile);
+        let end = ComplexSpace::from(&TileSpace {
+            index: Point {
+                x: tile.index.x + 1,
+                y: tile.index.y + 1,
+            },
+            zoom: z,
+        });
+
+        let step = Point {
+            x: (end.re - start.re) / self.size.x as f64,
+            y: (end.im - start.im) / self.size.y as f64,
+        };
+        (start, step)
+    }
+}
+
+impl Generator for GeneratorConfig {
+    fn generate(&self, tile: &TileSpace) -> Tile {
+        let (start, step) = self.texel_space(tile);
+
+        let mut data: Vec<Option<f32>> = vec![None; (self.size.x * self.size.y) as usize];
+
+        for y in 0..self.size.y {
+            for x in 0..self.size.x {
+                data[((y * self.size.x) + x) as usize] = mandel_smooth(
+                    self.max_iter,
+                    Complex64 {
+                        re: start.re + ((x as f64) * step.x),
+                        im: start.im + ((y as f64) * step.y),
+                    },
+                );
+            }
+        }
+
+        Tile { data }
+    }
+
+    // A single texel of generate, used to spot check tiles computed elsewhere
+    fn generate_texel(&self, tile: &TileSpace, x: usize, y: usize) -> Option<f32> {
+        let (start, step) = self.texel_space(tile);
+        mandel_smooth(
+            self.max_iter,
+            Complex64 {
+                re: start.re + ((x as f64) * step.x),
+                im: start.im + ((y as f64) * step.y),
+            },
+        )
+    }
+
+    fn size(&self) -> &Point<usize> {
+        &self.size
+    }
+
+    fn hash(&self, tile: &TileSpace) -> TileHash {
+        return format!(
+            "{}x{}-{}-x{}y{}z{}",
+            self.size.x, self.size.y, self.max_iter, tile.index.x, tile.index.y, tile.zoom
+        );
+    }
+
+    fn texel(&self, tile: &Tile, x: usize, y: usize) -> Option<f32> {
+        tile.data[(y * self.size.x) + x]
+    }
+}
+
+// Continuous (normalized) iteration count, so that samples can be
+// interpolated without banding
+// See https://en.wikipedia.org/wiki/Plotting_algorithms_for_the_Mandelbrot_set#Continuous_(smooth)_coloring
+// Escapes and interior points follow fractal_core::mandel_iter. Escaping at n
+// gives a value in (n, n + 1 + log2(1 / ln 2)), about n + 1.53, for |c| up to
+// e^2, which is wider than any view of the set. Points further out escape at
+// once with a large |z| and fall below n, and below 0 past |c| = e^4, where
+// RenderConfig clamps them to the first palette entry.
+pub fn mandel_smooth(max_iter: u64, c: Complex64) -> Option<f32> {
+    let (iter, z) = mandel_orbit(max_iter, Complex { re: c.re, im: c.im });
+    smooth(max_iter, iter, z)
+}
+
+// The smooth value of an orbit that stopped at iteration iter with z
+fn smooth(max_iter: u64, iter: u64, z: Complex) -> Option<f32> {
+    if max_iter == 0 || z.norm() <= 4.0 {
+        None
+    } else {
+        let log_zn = z.norm().ln() / 2.0;
+        Some((iter as f64 + 1.0 - log_zn.log2()) as f32)
+    }
+}
+
+impl TileStorage {
+    fn fetch(&mut self, tile: &TileSpace) -> TileHash {
+        let hash = self.generator.hash(tile);
+
+        if !self.storage.contains_key(&hash) {
+            let gen = self.generator.generate(tile);
+            self.storage.insert(String::clone(&hash), gen);
+        }
+        hash
+    }
+
+    // Reads an n x n block of texels starting at x, y of the given tile.
+    // Positions outside the tile are read from its neighbours so that
+    // filtering doesn't show seams at tile edges.
+    fn footprint(&mut self, tile: &TileSpace, x: i64, y: i64, n: usize) -> Vec<Option<f32>> {
+        let w = self.generator.size().x as i64;
+        let h = self.generator.size().y as i64;
+        let mut texels = Vec::with_capacity(n * n);
+
+        let inside = x >= 0 && y >= 0 && x + n as i64 <= w && y + n as i64 <= h;
+        if inside {
+            let hash = self.fetch(tile);
+            let data = &self.storage[&hash];
+            for j in 0..n as i64 {
+                for i in 0..n as i64 {
+                    texels.push(
+                        self.generator
+                            .texel(data, (x + i) as usize, (y + j) as usize),
+                    );
+                }
+            }
+            return texels;
+        }
+
+        for j in 0..n as i64 {
+            for i in 0..n as i64 {
+                let (tx, ty) = (x + i, y + j);
+                let neighbour = TileSpace {
+                    index: Point {
+                        x: tile.index.x + tx.div_euclid(w),
+                        y: tile.index.y + ty.div_euclid(h),
+                    },
+                    zoom: tile.zoom,
+                };
+                let hash = self.fetch(&neighbour);
+                texels.push(self.generator.texel(
+                    &self.storage[&hash],
+                    tx.rem_euclid(w) as usize,
+                    ty.rem_euclid(h) as usize,
+                ));
+            }
+        }
+        texels
+    }
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+// Catmull-Rom spline through p1 and p2
+fn cubic(p: &[f32], t: f32) -> f32 {
+    let (p0, p1, p2, p3) = (p[0], p[1], p[2], p[3]);
+    p1 + 0.5
+        * t
+        * (p2 - p0 + t * (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3 + t * (3.0 * (p1 - p2) + p3 - p0)))
+}
+
+impl TileManager for TileStorage {
+    fn sample(&mut self, location: ComplexSpace, zoom: usize) -> Option<f32> {
+        let sample = SampleSpace::from_complex(&location, zoom);
+
+        // Texels sit at integer positions, texel 0 being the tile origin
+        let size = self.generator.size();
+        let px = size.x as f32 * sample.coord.x;
+        let py = size.y as f32 * sample.coord.y;
+        let (x, y) = (px.floor() as i64, py.floor() as i64);
+        let (tx, ty) = (px - px.floor(), py - py.floor());
+
+        // The closest texel, past the halfway point to the next one it's that
+        let (nx, ny) = (px.round() as i64, py.round() as i64);
+        let nearest = |storage: &mut Self| storage.footprint(&sample.tile, nx, ny, 1)[0];
+
+        match self.filter {
+            SampleFilter::Nearest => nearest(self),
+            SampleFilter::Bilinear => {
+                let t = self.footprint(&sample.tile, x, y, 2);
+                // Interior points have no value to blend with
+                if t.iter().any(|v| v.is_none()) {
+                    return nearest(self);
+                }
+                let t: Vec<f32> = t.into_iter().map(Option::unwrap).collect();
+                Some(lerp(lerp(t[0], t[1], tx), lerp(t[2], t[3], tx), ty))
+            }
+            SampleFilter::Bicubic => {
+                let t = self.footprint(&sample.tile, x - 1, y - 1, 4);
+                if t.iter().any(|v| v.is_none()) {
+                    return nearest(self);
+                }
+                let t: Vec<f32> = t.into_iter().map(Option::unwrap).collect();
+                let rows: Vec<f32> = t.chunks(4).map(|row| cubic(row, tx)).collect();
+                // Catmull-Rom overshoots, keep within the inner 2x2 texels
+                let inner = [t[5], t[6], t[9], t[10]];
+                let min = inner.iter().cloned().fold(f32::INFINITY, f32::min);
+                let max = inner.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+                Some(cubic(&rows, ty).max(min).min(max))
+            }
+        }
+    }
+
+    fn tile_size(&self) -> &Point<usize> {
+        self.generator.size()
+    }
+
+    fn clear(&mut self) {
+        self.storage.clear();
+    }
+}
+
+// Level of detail
+// A tile at zoom z spans 1 / 2^z of complex space across tile_size texels.
+// The ideal level is the one where a texel is exactly one output pixel wide,
+// which is fractional for anything but power of two viewports. Levels stop at
+// MAX_ZOOM, past it f64 can't tell texels apart anyway.
+fn level_of_detail(pixel_size: f64, tile_size: usize) -> f64 {
+    let lod = -(pixel_size * tile_size as f64).log2();
+    lod.max(0.0).min(MAX_ZOOM as f64)
+}
+
+fn mix_one(from: u8, to: u8, t: f64) -> u8 {
+    (from as f64 + (to as f64 - from as f64) * t).round() as u8
+}
+
+fn mix(from: RGB8, to: RGB8, t: f64) -> RGB8 {
+    RGB8 {
+        r: mix_one(from.r, to.r, t),
+        g: mix_one(from.g, to.g, t),
+        b: mix_one(from.b, to.b, t),
+    }
+}
+
+impl RenderConfig {
+    // Smooth values fall between two palette entries
+    fn color(&self, value: Option<f32>) -> RGB8 {
+        match value {
+            None => self.bottom,
+            Some(value) => {
+                let len = self.palette.len();
+                let i = value.floor().max(0.0) as usize;
+                mix(
+                    self.palette[i % len],
+                    self.palette[(i + 1) % len],
+                    value.fract().max(0.0) as f64,
+                )
+            }
+        }
+    }
+}
+
+impl RenderConfig {
+    // Complex space corner of the first pixel, and the width of a pixel
+    fn view(&self, viewport: &ViewportConfig) -> (Point<f64>, f64) {
+        // How wide is the viewport in complex space
+        let complex_w = self.tile_width as f64 / (2.0 as f64).powf(viewport.zoom);
+        let complex_h = self.size.y as f64 / self.size.x as f64 * complex_w;
+
+        let step = complex_w / self.size.x as f64;
+
+        let start = Point {
+            x: viewport.center.re - complex_w / 2.0,
+            y: viewport.center.im - complex_h / 2.0,
+        };
+        (start, step)
+    }
+
+    // Tile level to sample for a pixel size, and how much of the next
+    // level to blend in
+    pub fn levels(&self, step: f64) -> (usize, f64) {
+        let lod = level_of_detail(step, self.manager.tile_size().x);
+        // Without blending snap to the finer level so that texels are never
+        // larger than pixels
+        if self.blend_levels {
+            (lod.floor() as usize, lod.fract())
+        } else {
+            (lod.ceil() as usize, 0.0)
+        }
+    }
+}
+
+impl PixelRenderer for RenderConfig {
+    fn render(&mut self, viewport: &ViewportConfig) -> Vec<RGB8> {
+        let (start, step) = self.view(viewport);
+        let (start_x, start_y) = (start.x, start.y);
+        let (level, blend) = self.levels(step);
+
+        let mut data: Vec<RGB8> =
+            vec![RGB8 { r: 0, g: 0, b: 0 }; (self.size.x * self.size.y) as usize];
+
+        for y in 0..self.size.y {
+            for x in 0..self.size.x {
+                let location = Complex64 {
+                    re: start_x + x as f64 * step,
+                    im: start_y + y as f64 * step,
+                };
+                let value = self.manager.sample(ComplexSpace(location), level);
+                let color = self.color(value);
+
+                data[((y * self.size.x) + x) as usize] = if blend > 0.0 {
+                    let fine = self.manager.sample(ComplexSpace(location), level + 1);
+                    mix(color, self.color(fine), blend)
+                } else {
+                    color
+                };
+            }
+        }
+
+        data
+    }
+}
+
+// Value following a --name flag
+pub fn arg(name: &str) -> Option<String> {
+    let flag = format!("--{}", name);
+    let mut args = env::args().skip_while(|a| *a != flag);
+    args.next().and_then(|_| args.next())
+}
+
+pub fn arg_or<T: FromStr>(name: &str, default: T) -> T
+where
+    T::Err: Debug,
+{
+    match arg(name).map(|v| v.parse()) {
+        None => default,
+        Some(Ok(v)) => v,
+        Some(Err(e)) => panic!("invalid --{}: {:?}", name, e),
+    }
+}
+
+pub fn generator(max_iter: u64) -> GeneratorConfig {
+    GeneratorConfig {
+        max_iter,
+        size: Point { x: 60, y: 60 },
+    }
+}
+
+pub fn renderer(
+    size: Point<usize>,
+    tile_width: f32,
+    max_iter: u64,
+    filter: SampleFilter,
+) -> RenderConfig {
+    renderer_with(HashMap::new(), size, tile_width, max_iter, filter)
+}
+
+// Renderer starting from already generated tiles
+pub fn renderer_with(
+    storage: HashMap<TileHash, Tile>,
+    size: Point<usize>,
+    tile_width: f32,
+    max_iter: u64,
+    filter: SampleFilter,
+) -> RenderConfig {
+    let manager = TileStorage {
+        generator: Box::new(SimdGenerator::new(generator(max_iter))),
+        storage,
+        filter,
+    };
+
+    RenderConfig {
+        manager: Box::new(manager),
+        palette: vec![
+            RGB8 {
+                r: 255,
+                g: 255,
+                b: 255,
+            },
+            RGB { r: 255, g: 0, b: 0 },
+        ],
+        bottom: RGB { r: 0, g: 0, b: 0 },
+        size,
+        tile_width,
+        blend_levels: true,
+    }
+}
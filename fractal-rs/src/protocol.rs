@@ -0,0 +1,249 @@
+// Distributed render wire protocol
+//
+// One message per binary websocket frame, see PROTOCOL.md for the layout.
+// Everything is little endian so browser workers can use a DataView directly.
+
+use std::io::{Read, Write};
+
+use tungstenite::{Message as Frame, WebSocket};
+
+use {Point, TileSpace};
+
+pub const VERSION: u8 = 1;
+
+// Interior texels are sent as this NaN
+const INTERIOR: u32 = 0x7fc0_0000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Capabilities {
+    pub version: u8,
+    pub threads: u16,
+    pub precision: u8, // bits of float precision in the inner loop
+    pub formulas: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobSpec {
+    pub id: u64,
+    pub tile: TileSpace,
+    pub size: Point<usize>,
+    pub max_iter: u64,
+    pub formula: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    // Worker -> coordinator
+    Hello(Capabilities),
+    Request { count: u16 },
+    Result { job: u64, data: Vec<Option<f32>> },
+    // Coordinator -> worker
+    Job(JobSpec),
+    Idle { retry_ms: u32 },
+    Goodbye { reason: String },
+}
+
+const HELLO: u8 = 0x01;
+const REQUEST: u8 = 0x02;
+const RESULT: u8 = 0x03;
+const JOB: u8 = 0x81;
+const IDLE: u8 = 0x82;
+const GOODBYE: u8 = 0x83;
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, v: u8) {
+        self.0.push(v);
+    }
+    fn u16(&mut self, v: u16) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+    fn u32(&mut self, v: u32) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+    fn u64(&mut self, v: u64) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+    fn i64(&mut self, v: i64) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+    // u8 length prefixed utf8, longer strings are cut at the last whole
+    // character within 255 bytes
+    fn str(&mut self, v: &str) {
+        let mut end = v.len().min(255);
+        while !v.is_char_boundary(end) {
+            end -= 1;
+        }
+        let bytes = &v.as_bytes()[..end];
+        self.u8(bytes.len() as u8);
+        self.0.extend_from_slice(bytes);
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.0.len() < n {
+            return Err("message truncated".to_string());
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Ok(head)
+    }
+    fn array<T: Default + AsMut<[u8]>>(&mut self) -> Result<T, String> {
+        let mut array = T::default();
+        let n = array.as_mut().len();
+        array.as_mut().copy_from_slice(self.take(n)?);
+        Ok(array)
+    }
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+    fn i64(&mut self) -> Result<i64, String> {
+        Ok(i64::from_le_bytes(self.array()?))
+    }
+    fn str(&mut self) -> Result<String, String> {
+        let len = self.u8()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|e| e.to_string())
+    }
+}
+
+impl Message {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut w = Writer(vec![]);
+        match self {
+            Message::Hello(caps) => {
+                w.u8(HELLO);
+                w.u8(caps.version);
+                w.u16(caps.threads);
+                w.u8(caps.precision);
+                w.u8(caps.formulas.len() as u8);
+                for formula in &caps.formulas {
+                    w.str(formula);
+                }
+            }
+            Message::Request { count } => {
+                w.u8(REQUEST);
+                w.u16(*count);
+            }
+            Message::Result { job, data } => {
+                w.u8(RESULT);
+                w.u64(*job);
+                w.u32(data.len() as u32);
+                for texel in data {
+                    w.u32(texel.map_or(INTERIOR, f32::to_bits));
+                }
+            }
+            Message::Job(job) => {
+                w.u8(JOB);
+                w.u64(job.id);
+                w.i64(job.tile.index.x);
+                w.i64(job.tile.index.y);
+                w.u32(job.tile.zoom as u32);
+                w.u32(job.size.x as u32);
+                w.u32(job.size.y as u32);
+                w.u64(job.max_iter);
+                w.str(&job.formula);
+            }
+            Message::Idle { retry_ms } => {
+                w.u8(IDLE);
+                w.u32(*retry_ms);
+            }
+            Message::Goodbye { reason } => {
+                w.u8(GOODBYE);
+                w.str(reason);
+            }
+        }
+        w.0
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Message, String> {
+        let mut r = Reader(data);
+        let message = match r.u8()? {
+            HELLO => {
+                let version = r.u8()?;
+                let threads = r.u16()?;
+                let precision = r.u8()?;
+                let count = r.u8()?;
+                let formulas = (0..count).map(|_| r.str()).collect::<Result<_, _>>()?;
+                Message::Hello(Capabilities {
+                    version,
+                    threads,
+                    precision,
+                    formulas,
+                })
+            }
+            REQUEST => Message::Request { count: r.u16()? },
+            RESULT => {
+                let job = r.u64()?;
+                let len = r.u32()? as usize;
+                let mut data = Vec::with_capacity(len.min(r.0.len() / 4));
+                for _ in 0..len {
+                    let bits = r.u32()?;
+                    let texel = f32::from_bits(bits);
+                    data.push(if texel.is_nan() { None } else { Some(texel) });
+                }
+                Message::Result { job, data }
+            }
+            JOB => Message::Job(JobSpec {
+                id: r.u64()?,
+                tile: TileSpace {
+                    index: Point {
+                        x: r.i64()?,
+                        y: r.i64()?,
+                    },
+                    zoom: r.u32()? as usize,
+                },
+                size: Point {
+                    x: r.u32()? as usize,
+                    y: r.u32()? as usize,
+                },
+                max_iter: r.u64()?,
+                formula: r.str()?,
+            }),
+            IDLE => Message::Idle { retry_ms: r.u32()? },
+            GOODBYE => Message::Goodbye { reason: r.str()? },
+            tag => return Err(format!("unknown message type {:#04x}", tag)),
+        };
+        if !r.0.is_empty() {
+            return Err("trailing bytes after message".to_string());
+        }
+        Ok(message)
+    }
+}
+
+// Whatever carries messages between a worker and the coordinator
+pub trait Transport {
+    fn send(&mut self, message: &Message) -> Result<(), String>;
+    // None once the other side has closed the connection
+    fn receive(&mut self) -> Result<Option<Message>, String>;
+}
+
+impl<S: Read + Write> Transport for WebSocket<S> {
+    fn send(&mut self, message: &Message) -> Result<(), String> {
+        self.write_message(Frame::Binary(message.encode()))
+            .map_err(|e| e.to_string())
+    }
+
+    fn receive(&mut self) -> Result<Option<Message>, String> {
+        loop {
+            match self.read_message().map_err(|e| e.to_string())? {
+                Frame::Binary(data) => return Message::decode(&data).map(Some),
+                Frame::Close(_) => return Ok(None),
+                // Pings are answered by tungstenite
+                _ => continue,
+            }
+        }
+    }
+}
@@ -0,0 +1,172 @@
+// Rendering scene files, see fractal_core::Scene for the format
+//
+// Smooth scenes go through the tiled renderer like everything else here,
+// banded ones are rendered per pixel with fractal_core::render_frame so they
+// match what the wasm renderers showed when exporting them.
+
+use std::collections::HashMap;
+use std::fs;
+
+use fractal_core::{render_frame, Colouring, Formula, Scene, Viewport, BOTTOM, RGB, SCENE_WIDTH};
+use num::complex::Complex64;
+use rgb::RGB8;
+
+use {
+    renderer_with, ComplexSpace, PixelRenderer, Point, RenderConfig, SampleFilter, Tile, TileHash,
+    ViewportConfig,
+};
+
+pub fn load(path: &str) -> Result<Scene, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("failed to read {}: {}", path, e))?;
+    text.parse()
+        .map_err(|e| format!("invalid scene {}: {}", path, e))
+}
+
+// The whole set at 300x300 in white and red, what fractal-rs renders without
+// a scene
+pub fn default_scene(max_iter: u64) -> Scene {
+    let viewport = ViewportConfig {
+        center: ComplexSpace(Complex64 { re: -0.5, im: 0.0 }),
+        zoom: 0.5,
+    };
+    let palette = [
+        RGB8 {
+            r: 255,
+            g: 255,
+            b: 255,
+        },
+        RGB8 { r: 255, g: 0, b: 0 },
+    ];
+    from_view(&viewport, &Point { x: 300, y: 300 }, max_iter, &palette)
+}
+
+// The scene's view at f64 precision
+pub fn viewport(scene: &Scene) -> Result<ViewportConfig, String> {
+    let number = |s: &str| {
+        s.parse::<f64>()
+            .map_err(|e| format!("invalid number {:?}: {}", s, e))
+    };
+    Ok(ViewportConfig {
+        center: ComplexSpace(Complex64 {
+            re: number(&scene.center_re)?,
+            im: number(&scene.center_im)?,
+        }),
+        zoom: number(&scene.zoom)?,
+    })
+}
+
+// Scene for a view rendered with renderer()'s defaults, the view's digits are
+// an f64's
+pub fn from_view(
+    viewport: &ViewportConfig,
+    size: &Point<usize>,
+    max_iter: u64,
+    palette: &[RGB8],
+) -> Scene {
+    Scene {
+        formula: Formula::Mandelbrot,
+        center_re: viewport.center.re.to_string(),
+        center_im: viewport.center.im.to_string(),
+        zoom: viewport.zoom.to_string(),
+        max_iter,
+        width: size.x as u32,
+        height: size.y as u32,
+        supersample: 1,
+        colouring: Colouring::Smooth,
+        palette: palette.iter().map(|c| RGB::rgb(c.r, c.g, c.b)).collect(),
+    }
+}
+
+// Tiled renderer for a smooth scene, starting from already generated tiles.
+// It renders the supersampled size, see downsample.
+pub fn renderer(
+    scene: &Scene,
+    storage: HashMap<TileHash, Tile>,
+    filter: SampleFilter,
+) -> RenderConfig {
+    let mut renderer = renderer_with(
+        storage,
+        supersampled(scene),
+        SCENE_WIDTH as f32,
+        scene.max_iter,
+        filter,
+    );
+    renderer.palette = scene.palette.iter().map(rgb8).collect();
+    renderer.bottom = rgb8(&BOTTOM);
+    renderer
+}
+
+fn supersampled(scene: &Scene) -> Point<usize> {
+    let n = scene.supersample as usize;
+    Point {
+        x: scene.width as usize * n,
+        y: scene.height as usize * n,
+    }
+}
+
+// width x height pixels, filter is only used by smooth scenes
+pub fn render(scene: &Scene, filter: SampleFilter) -> Result<Vec<RGB8>, String> {
+    let viewport = viewport(scene)?;
+    let size = supersampled(scene);
+
+    let pixels = match scene.colouring {
+        Colouring::Smooth => renderer(scene, HashMap::new(), filter).render(&viewport),
+        Colouring::Bands => {
+            let view = Viewport {
+                center_re: viewport.center.re,
+                center_im: viewport.center.im,
+                width: SCENE_WIDTH / 2f64.powf(viewport.zoom),
+            };
+            let (w, h) = (size.x as u32, size.y as u32);
+            let mut buf = vec![BOTTOM; size.x * size.y];
+            render_frame(
+                &mut buf,
+                w,
+                h,
+                &view.frame(w, h),
+                scene.max_iter,
+                &scene.palette,
+            );
+            buf.iter().map(rgb8).collect()
+        }
+    };
+    Ok(downsample(scene, &pixels))
+}
+
+fn rgb8(c: &RGB) -> RGB8 {
+    RGB8 {
+        r: c.r,
+        g: c.g,
+        b: c.b,
+    }
+}
+
+// Averages the supersampled pixels down to the scene's size
+pub fn downsample(scene: &Scene, pixels: &[RGB8]) -> Vec<RGB8> {
+    let (width, height) = (scene.width as usize, scene.height as usize);
+    let n = scene.supersample as usize;
+    if n == 1 {
+        return pixels.to_vec();
+    }
+    let mut out = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = [0usize; 3];
+            for j in 0..n {
+                for i in 0..n {
+                    let p = pixels[(y * n + j) * width * n + x * n + i];
+                    sum[0] += p.r as usize;
+                    sum[1] += p.g as usize;
+                    sum[2] += p.b as usize;
+                }
+            }
+            let mean = |s: usize| ((s + n * n / 2) / (n * n)) as u8;
+            out.push(RGB8 {
+                r: mean(sum[0]),
+                g: mean(sum[1]),
+                b: mean(sum[2]),
+            });
+        }
+    }
+    out
+}
@@ -0,0 +1,276 @@
+// Local HTTP tile server
+//
+// Answers GET /{formula}/{z}/{x}/{y}.png with the same tiles `export` writes,
+// generating them on demand. The most recently used PNG_CAPACITY encoded pngs
+// are kept, and the TileStorage tiles behind them are dropped every
+// PNG_CAPACITY renders so memory stays bounded.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+use fractal_core::MAX_ZOOM;
+
+use export::{render_tile, TILE_PIXELS, WORLD_SIZE};
+use {arg, arg_or, renderer, Point, RenderConfig, SampleFilter, MAX_ITER};
+
+// Every tile is deterministic for its key, so clients can keep them forever
+const CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+
+// Connections are handled one at a time, so a client that stops sending only
+// holds up the others this long
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+// About 30MB of encoded tiles
+const PNG_CAPACITY: usize = 1024;
+
+struct TileRequest {
+    formula: String,
+    z: u32,
+    x: u64,
+    y: u64,
+}
+
+impl TileRequest {
+    // /{formula}/{z}/{x}/{y}.png
+    fn parse(path: &str) -> Option<Self> {
+        let parts: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+        if parts.len() != 4 || !parts[3].ends_with(".png") {
+            return None;
+        }
+        let z: u32 = parts[1].parse().ok()?;
+        let x: u64 = parts[2].parse().ok()?;
+        let y: u64 = parts[3].trim_end_matches(".png").parse().ok()?;
+        // Deeper tiles would need more precision than f64 has
+        if z > MAX_ZOOM || x >> z != 0 || y >> z != 0 {
+            return None;
+        }
+        Some(TileRequest {
+            formula: parts[0].to_string(),
+            z,
+            x,
+            y,
+        })
+    }
+}
+
+struct Response {
+    status: &'static str,
+    headers: Vec<(&'static str, String)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn empty(status: &'static str) -> Self {
+        Response {
+            status,
+            headers: vec![],
+            body: vec![],
+        }
+    }
+
+    fn write_to(&self, stream: &mut TcpStream, head_only: bool) -> std::io::Result<()> {
+        let mut head = format!("HTTP/1.1 {}\r\n", self.status);
+        head.push_str("Access-Control-Allow-Origin: *\r\n");
+        head.push_str("Connection: close\r\n");
+        head.push_str(&format!("Content-Length: {}\r\n", self.body.len()));
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str("\r\n");
+        stream.write_all(head.as_bytes())?;
+        if !head_only {
+            stream.write_all(&self.body)?;
+        }
+        stream.flush()
+    }
+}
+
+// FNV-1a, stable across builds unlike DefaultHasher
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+struct TileServer {
+    // One renderer, and with it one TileStorage, per formula
+    formulas: HashMap<String, RenderConfig>,
+    // Encoded tiles with when they were last served, see PNG_CAPACITY
+    pngs: HashMap<String, (Vec<u8>, u64)>,
+    clock: u64,
+    // pngs rendered since the formulas' tiles were last dropped
+    rendered: usize,
+    max_iter: u64,
+    filter: SampleFilter,
+}
+
+impl TileServer {
+    // Everything a tile's pixels depend on, as they're cached for good
+    fn key(&self, tile: &TileRequest) -> String {
+        let renderer = &self.formulas[&tile.formula];
+        format!(
+            "{}-{}-{}-{:?}-{:?}-{}-{}-{:?}-{:?}-{}/{}/{}",
+            env!("CARGO_PKG_VERSION"),
+            tile.formula,
+            self.max_iter,
+            self.filter,
+            renderer.manager.tile_size(),
+            renderer.blend_levels,
+            TILE_PIXELS,
+            renderer.palette,
+            renderer.bottom,
+            tile.z,
+            tile.x,
+            tile.y
+        )
+    }
+
+    fn png(&mut self, tile: &TileRequest, key: &str) -> Option<&Vec<u8>> {
+        self.clock += 1;
+        if let Some(png) = self.pngs.get_mut(key) {
+            png.1 = self.clock;
+        } else {
+            let renderer = self.formulas.get_mut(&tile.formula)?;
+            let pixels = render_tile(renderer, tile.z, tile.x, tile.y);
+            self.rendered += 1;
+            if self.rendered >= PNG_CAPACITY {
+                for renderer in self.formulas.values_mut() {
+                    renderer.manager.clear();
+                }
+                self.rendered = 0;
+            }
+            let png = match lodepng::encode_memory(
+                &pixels,
+                TILE_PIXELS,
+                TILE_PIXELS,
+                lodepng::ColorType::RGB,
+                8,
+            ) {
+                Ok(png) => png,
+                Err(e) => panic!("failed to encode png: {:?}", e),
+            };
+            if self.pngs.len() >= PNG_CAPACITY {
+                let oldest = self
+                    .pngs
+                    .iter()
+                    .min_by_key(|(_, (_, used))| *used)
+                    .map(|(k, _)| k.clone());
+                if let Some(k) = oldest {
+                    self.pngs.remove(&k);
+                }
+            }
+            self.pngs.insert(key.to_string(), (png, self.clock));
+        }
+        self.pngs.get(key).map(|(png, _)| png)
+    }
+
+    fn respond(&mut self, path: &str, if_none_match: Option<&str>) -> Response {
+        let tile = match TileRequest::parse(path) {
+            Some(tile) => tile,
+            None => return Response::empty("404 Not Found"),
+        };
+        if !self.formulas.contains_key(&tile.formula) {
+            return Response::empty("404 Not Found");
+        }
+
+        let key = self.key(&tile);
+        let etag = format!("\"{:016x}\"", fnv1a(key.as_bytes()));
+        let headers = vec![
+            ("ETag", etag.clone()),
+            ("Cache-Control", CACHE_CONTROL.to_string()),
+        ];
+
+        if if_none_match == Some(etag.as_str()) {
+            return Response {
+                status: "304 Not Modified",
+                headers,
+                body: vec![],
+            };
+        }
+
+        let body = self.png(&tile, &key).cloned().unwrap_or_default();
+        Response {
+            status: "200 OK",
+            headers: [headers, vec![("Content-Type", "image/png".to_string())]].concat(),
+            body,
+        }
+    }
+
+    fn handle(&mut self, mut stream: TcpStream) -> std::io::Result<()> {
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+        stream.set_write_timeout(Some(READ_TIMEOUT))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        let mut parts = request_line.split_whitespace();
+        let method = parts.next().unwrap_or("");
+        let path = parts.next().unwrap_or("");
+
+        let mut if_none_match = None;
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+                break;
+            }
+            let mut header = line.splitn(2, ':');
+            let name = header.next().unwrap_or("").trim();
+            if name.eq_ignore_ascii_case("if-none-match") {
+                if_none_match = header.next().map(|v| v.trim().to_string());
+            }
+        }
+
+        let response = match method {
+            "GET" | "HEAD" => self.respond(path, if_none_match.as_deref()),
+            _ => Response::empty("405 Method Not Allowed"),
+        };
+        println!("{} {} {}", method, path, response.status);
+        response.write_to(&mut stream, method == "HEAD")
+    }
+}
+
+// fractal-rs serve --bind 127.0.0.1:8080
+pub fn run() {
+    let bind = arg("bind").unwrap_or_else(|| "127.0.0.1:8080".to_string());
+    let listener = match TcpListener::bind(&bind) {
+        Ok(listener) => listener,
+        Err(e) => panic!("failed to bind {}: {}", bind, e),
+    };
+
+    let max_iter = arg_or("max-iter", MAX_ITER);
+    let filter = arg_or("filter", SampleFilter::Bilinear);
+    let mut formulas = HashMap::new();
+    formulas.insert(
+        "mandelbrot".to_string(),
+        renderer(
+            Point {
+                x: TILE_PIXELS,
+                y: TILE_PIXELS,
+            },
+            WORLD_SIZE as f32,
+            max_iter,
+            filter,
+        ),
+    );
+    let mut server = TileServer {
+        formulas,
+        pngs: HashMap::new(),
+        clock: 0,
+        rendered: 0,
+        max_iter,
+        filter,
+    };
+
+    println!(
+        "Serving tiles on http://{}/mandelbrot/{{z}}/{{x}}/{{y}}.png",
+        bind
+    );
+    for stream in listener.incoming() {
+        let result = stream.and_then(|stream| server.handle(stream));
+        if let Err(e) = result {
+            println!("Connection failed: {}", e);
+        }
+    }
+}
@@ -0,0 +1,110 @@
+// Native worker for the distributed render protocol
+//
+// Pulls as many jobs as it has threads, generates the tiles in parallel with
+// SimdGenerator, which matches the GeneratorConfig the coordinator uses to
+// check them exactly, and sends the texels back.
+
+use std::thread;
+use std::time::Duration;
+
+use tungstenite::connect;
+
+use coordinator::FORMULA;
+use protocol::{Capabilities, JobSpec, Message, Transport, VERSION};
+use simd::SimdGenerator;
+use {arg, arg_or, Generator, GeneratorConfig, Tile};
+
+pub fn capabilities(threads: usize) -> Capabilities {
+    Capabilities {
+        version: VERSION,
+        threads: threads as u16,
+        precision: 64,
+        formulas: vec![FORMULA.to_string()],
+    }
+}
+
+fn generate(job: &JobSpec) -> Result<Tile, String> {
+    if job.formula != FORMULA {
+        return Err(format!("unsupported formula {}", job.formula));
+    }
+    let generator = SimdGenerator::new(GeneratorConfig {
+        size: job.size.clone(),
+        max_iter: job.max_iter,
+    });
+    Ok(generator.generate(&job.tile))
+}
+
+// Works until the coordinator says goodbye, returning the number of jobs done
+pub fn work<T: Transport>(transport: &mut T, threads: usize) -> Result<usize, String> {
+    let threads = threads.max(1).min(u16::MAX as usize);
+    transport.send(&Message::Hello(capabilities(threads)))?;
+
+    let mut done = 0;
+    loop {
+        transport.send(&Message::Request {
+            count: threads as u16,
+        })?;
+
+        // The reply is up to `threads` jobs, followed by an Idle if fewer
+        // than that were queued
+        let mut jobs = vec![];
+        let mut retry = None;
+        while jobs.len() < threads {
+            match transport.receive()? {
+                Some(Message::Job(job)) => jobs.push(job),
+                Some(Message::Idle { retry_ms }) => {
+                    retry = Some(Duration::from_millis(retry_ms as u64));
+                    break;
+                }
+                Some(Message::Goodbye { ref reason }) if reason == "done" => return Ok(done),
+                Some(Message::Goodbye { reason }) => return Err(reason),
+                Some(m) => return Err(format!("unexpected {:?}", m)),
+                None => return Err("coordinator hung up".to_string()),
+            }
+        }
+
+        let tiles: Vec<Result<Tile, String>> = thread::scope(|scope| {
+            let handles: Vec<_> = jobs
+                .iter()
+                .map(|job| scope.spawn(move || generate(job)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| {
+                    h.join()
+                        .unwrap_or_else(|_| Err("tile panicked".to_string()))
+                })
+                .collect()
+        });
+
+        for (job, tile) in jobs.iter().zip(tiles) {
+            transport.send(&Message::Result {
+                job: job.id,
+                data: tile?.data,
+            })?;
+        }
+        done += jobs.len();
+
+        if let (true, Some(retry)) = (jobs.is_empty(), retry) {
+            thread::sleep(retry);
+        }
+    }
+}
+
+// fractal-worker --connect ws://127.0.0.1:9000 --threads 4
+pub fn run() {
+    let url = arg("connect").unwrap_or_else(|| "ws://127.0.0.1:9000".to_string());
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let threads = arg_or("threads", threads);
+
+    let mut socket = match connect(url.as_str()) {
+        Ok((socket, _)) => socket,
+        Err(e) => panic!("failed to connect to {}: {}", url, e),
+    };
+    println!("Connected to {} with {} threads", url, threads);
+
+    match work(&mut socket, threads) {
+        Ok(done) => println!("Finished after {} jobs", done),
+        Err(e) => panic!("worker failed: {}", e),
+    }
+}
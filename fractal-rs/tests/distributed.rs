@@ -0,0 +1,153 @@
+extern crate fractal_rs;
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use fractal_rs::coordinator::{plan, Coordinator, LocalCoordinator, Session};
+use fractal_rs::protocol::{JobSpec, Message};
+use fractal_rs::worker::{capabilities, work};
+use fractal_rs::*;
+
+fn small_generator() -> GeneratorConfig {
+    GeneratorConfig {
+        size: Point { x: 16, y: 16 },
+        max_iter: 200,
+    }
+}
+
+fn small_renderer() -> RenderConfig {
+    RenderConfig {
+        manager: Box::new(TileStorage {
+            generator: Box::new(small_generator()),
+            storage: HashMap::new(),
+            filter: SampleFilter::Bilinear,
+        }),
+        palette: vec![rgb::RGB8 { r: 255, g: 0, b: 0 }],
+        bottom: rgb::RGB8 { r: 0, g: 0, b: 0 },
+        size: Point { x: 40, y: 30 },
+        tile_width: 3.0,
+        blend_levels: true,
+    }
+}
+
+fn viewport() -> ViewportConfig {
+    ViewportConfig {
+        center: ComplexSpace(num::complex::Complex64 { re: -0.5, im: 0.0 }),
+        zoom: 0.5,
+    }
+}
+
+fn coordinator(timeout: Duration) -> Coordinator {
+    let tiles = plan(&small_renderer(), &viewport());
+    Coordinator::new(small_generator(), tiles, timeout)
+}
+
+#[test]
+fn messages_round_trip() {
+    let messages = vec![
+        Message::Hello(capabilities(8)),
+        Message::Request { count: 3 },
+        Message::Result {
+            job: 7,
+            data: vec![Some(1.5), None, Some(-0.25)],
+        },
+        Message::Job(JobSpec {
+            id: u64::MAX,
+            tile: TileSpace {
+                index: Point { x: -3, y: 4 },
+                zoom: 12,
+            },
+            size: Point { x: 60, y: 60 },
+            max_iter: 5000,
+            formula: "mandelbrot".to_string(),
+        }),
+        Message::Idle { retry_ms: 250 },
+        Message::Goodbye {
+            reason: "done".to_string(),
+        },
+    ];
+    for message in messages {
+        assert_eq!(Message::decode(&message.encode()), Ok(message));
+    }
+    assert!(Message::decode(&[0x02, 0x01]).is_err());
+    assert!(Message::decode(&[0x7f]).is_err());
+}
+
+#[test]
+fn long_strings_are_cut_between_characters() {
+    // 254 bytes of ascii then a 2 byte character straddling the limit
+    let reason = format!("{}é and more", "a".repeat(254));
+    let goodbye = Message::Goodbye { reason };
+    match Message::decode(&goodbye.encode()) {
+        Ok(Message::Goodbye { reason }) => assert_eq!(reason, "a".repeat(254)),
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn worker_completes_render() {
+    let coordinator = Mutex::new(coordinator(Duration::from_secs(30)));
+    let jobs = coordinator.lock().unwrap().remaining();
+    assert!(jobs > 0);
+
+    let done = work(&mut LocalCoordinator::new(&coordinator), 3).unwrap();
+    assert_eq!(done, jobs);
+    assert_eq!(coordinator.lock().unwrap().remaining(), 0);
+
+    // Every planned tile came back identical to a local render
+    let generator = small_generator();
+    let results = coordinator.lock().unwrap().take_results();
+    for tile in plan(&small_renderer(), &viewport()) {
+        let remote = &results[&generator.hash(&tile)];
+        assert_eq!(remote.data, generator.generate(&tile).data);
+    }
+}
+
+#[test]
+fn bad_results_are_rejected_and_requeued() {
+    let coordinator = Mutex::new(coordinator(Duration::from_secs(30)));
+    let jobs = coordinator.lock().unwrap().remaining();
+
+    let mut session = Session::new(&coordinator);
+    session.handle(Message::Hello(capabilities(1))).unwrap();
+    let job = match session.handle(Message::Request { count: 1 }).unwrap()[..] {
+        [Message::Job(ref job)] => job.clone(),
+        ref m => panic!("expected a job, got {:?}", m),
+    };
+
+    let data = vec![Some(1.0); job.size.x * job.size.y];
+    let reply = session
+        .handle(Message::Result { job: job.id, data })
+        .unwrap();
+    match reply[..] {
+        [Message::Goodbye { .. }] => {}
+        ref m => panic!("expected goodbye, got {:?}", m),
+    }
+    assert!(session.is_closed());
+    drop(session);
+
+    // The job goes to the next worker
+    assert_eq!(coordinator.lock().unwrap().remaining(), jobs);
+    let done = work(&mut LocalCoordinator::new(&coordinator), 1).unwrap();
+    assert_eq!(done, jobs);
+}
+
+#[test]
+fn timed_out_jobs_are_reassigned() {
+    let timeout = Duration::from_secs(30);
+    let mut coordinator = coordinator(timeout);
+    let slow = coordinator.connect(capabilities(1)).unwrap();
+    let fast = coordinator.connect(capabilities(1)).unwrap();
+
+    let start = Instant::now();
+    let total = coordinator.remaining() as u16;
+    let taken = coordinator.request(slow, total, start);
+    assert_eq!(taken.len(), total as usize);
+    assert!(coordinator.request(fast, 1, start).is_empty());
+
+    let later = start + timeout + Duration::from_secs(1);
+    let retaken = coordinator.request(fast, total, later);
+    assert_eq!(retaken.len(), total as usize);
+    assert_eq!(retaken[0].tile, taken[0].tile);
+}
@@ -0,0 +1,135 @@
+extern crate fractal_core;
+extern crate fractal_rs;
+extern crate num;
+
+use fractal_core::{default_palette, render_frame, Complex, Frame, BOTTOM, RGB};
+use fractal_rs::*;
+use num::complex::Complex64;
+
+const MAX_ITER: u64 = 200;
+
+// Power of two sizes keep every texel position exact in both crates
+fn generator() -> GeneratorConfig {
+    GeneratorConfig {
+        size: Point { x: 16, y: 16 },
+        max_iter: MAX_ITER,
+    }
+}
+
+fn tiles() -> Vec<TileSpace> {
+    let mut tiles = vec![];
+    for zoom in 0..3 {
+        let n = 1 << zoom;
+        for y in -2 * n..2 * n {
+            for x in -3 * n..2 * n {
+                tiles.push(TileSpace {
+                    index: Point { x, y },
+                    zoom,
+                });
+            }
+        }
+    }
+    tiles
+}
+
+// Texel (x, y) of a tile, as described in PROTOCOL.md
+fn texel_point(tile: &TileSpace, x: usize, y: usize, size: &Point<usize>) -> Complex {
+    let scale = (1u64 << tile.zoom) as f64;
+    Complex {
+        re: (tile.index.x as f64 + x as f64 / size.x as f64) / scale,
+        im: (tile.index.y as f64 + y as f64 / size.y as f64) / scale,
+    }
+}
+
+#[test]
+fn smooth_values_follow_escape_count() {
+    for y in 0..=64 {
+        for x in 0..=96 {
+            let c = Complex {
+                re: -2.5 + x as f64 * 3.5 / 96.0,
+                im: -1.25 + y as f64 * 2.5 / 64.0,
+            };
+            let smooth = mandel_smooth(MAX_ITER, Complex64 { re: c.re, im: c.im });
+            match mandel_iter(MAX_ITER, c) {
+                None => assert_eq!(smooth, None, "{:?}", c),
+                Some(n) => {
+                    let smooth = smooth.expect("escaped point has a smooth value");
+                    assert!(
+                        smooth > n as f32 && smooth < n as f32 + 1.53,
+                        "{} escaped at {} for {:?}",
+                        smooth,
+                        n,
+                        c
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn far_points_fall_below_their_escape_count() {
+    let smooth = |re: f64| mandel_smooth(MAX_ITER, Complex64 { re, im: 0.0 }).unwrap();
+    assert!(smooth(7.0) > 1.0);
+    assert!(smooth(8.0) < 1.0);
+    assert!(smooth(60.0) < 0.0);
+}
+
+#[test]
+fn tiles_match_core_escape_count() {
+    let generator = generator();
+    let size = generator.size.clone();
+    for tile in tiles() {
+        let data = generator.generate(&tile);
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let texel = generator.texel(&data, x, y);
+                assert_eq!(texel, generator.generate_texel(&tile, x, y));
+                let escape = mandel_iter(MAX_ITER, texel_point(&tile, x, y, &size));
+                assert_eq!(
+                    texel.is_none(),
+                    escape.is_none(),
+                    "texel {},{} of {:?}",
+                    x,
+                    y,
+                    tile
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn wasm_frames_match_tiles() {
+    let generator = generator();
+    let size = generator.size.clone();
+    let palette: Vec<RGB> = default_palette()
+        .into_iter()
+        .filter(|colour| *colour != BOTTOM)
+        .collect();
+
+    for tile in tiles() {
+        // The frame the wasm renderers would draw over this tile
+        let frame = Frame {
+            start: texel_point(&tile, 0, 0, &size),
+            step: 1.0 / (size.x as f64 * (1u64 << tile.zoom) as f64),
+        };
+        let (w, h) = (size.x as u32, size.y as u32);
+        let mut buf = vec![BOTTOM; size.x * size.y];
+        render_frame(&mut buf, w, h, &frame, MAX_ITER, &palette);
+
+        let data = generator.generate(&tile);
+        for y in 0..size.y {
+            for x in 0..size.x {
+                assert_eq!(
+                    buf[y * size.x + x] == BOTTOM,
+                    generator.texel(&data, x, y).is_none(),
+                    "pixel {},{} of {:?}",
+                    x,
+                    y,
+                    tile
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,224 @@
+extern crate fractal_rs;
+extern crate lodepng;
+extern crate num;
+extern crate rgb;
+
+use fractal_rs::*;
+use num::complex::Complex64;
+use rgb::RGB8;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+// Canonical views rendered with fixed settings and compared to the images in
+// tests/golden. Run with GOLDEN_UPDATE=1 to write new references after a
+// deliberate change, and check them in.
+
+const SIZE: usize = 96;
+
+// Per pixel colour distance that counts as visible, see distance
+const TOLERANCE: f64 = 24.0;
+// Fraction of pixels allowed past TOLERANCE, for float noise at the edges
+// of escape bands
+const ALLOWED: f64 = 0.005;
+
+struct Scene {
+    name: &'static str,
+    re: f64,
+    im: f64,
+    zoom: f64,
+    max_iter: u64,
+}
+
+const SCENES: [Scene; 4] = [
+    Scene {
+        name: "full-set",
+        re: -0.5,
+        im: 0.0,
+        zoom: 0.0,
+        max_iter: 200,
+    },
+    Scene {
+        name: "seahorse-valley",
+        re: -0.745,
+        im: 0.11,
+        zoom: 7.0,
+        max_iter: 300,
+    },
+    Scene {
+        name: "mini-brot",
+        re: -1.7548776662466927,
+        im: 0.0,
+        zoom: 6.0,
+        max_iter: 400,
+    },
+    Scene {
+        name: "deep-point",
+        re: -0.743643887037151,
+        im: 0.13182590420533,
+        zoom: 20.0,
+        max_iter: 1000,
+    },
+];
+
+// GeneratorConfig behind TileStorage behind RenderConfig, spelled out so that
+// changing renderer()'s defaults doesn't move the references
+fn render(scene: &Scene) -> Vec<RGB8> {
+    let manager = TileStorage {
+        generator: Box::new(GeneratorConfig {
+            size: Point { x: 32, y: 32 },
+            max_iter: scene.max_iter,
+        }),
+        storage: HashMap::new(),
+        filter: SampleFilter::Bilinear,
+    };
+    let mut renderer = RenderConfig {
+        manager: Box::new(manager),
+        palette: vec![
+            RGB8 { r: 0, g: 7, b: 100 },
+            RGB8 {
+                r: 32,
+                g: 107,
+                b: 203,
+            },
+            RGB8 {
+                r: 237,
+                g: 255,
+                b: 255,
+            },
+            RGB8 {
+                r: 255,
+                g: 170,
+                b: 0,
+            },
+            RGB8 { r: 0, g: 2, b: 0 },
+        ],
+        bottom: RGB8 { r: 0, g: 0, b: 0 },
+        size: Point { x: SIZE, y: SIZE },
+        tile_width: 3.0,
+        blend_levels: true,
+    };
+    renderer.render(&ViewportConfig {
+        center: ComplexSpace(Complex64 {
+            re: scene.re,
+            im: scene.im,
+        }),
+        zoom: scene.zoom,
+    })
+}
+
+// "Redmean" weighted RGB distance, a cheap stand in for a perceptual colour
+// difference. Ranges from 0 to about 765.
+fn distance(a: RGB8, b: RGB8) -> f64 {
+    let mean = (a.r as f64 + b.r as f64) / 2.0;
+    let (dr, dg, db) = (
+        a.r as f64 - b.r as f64,
+        a.g as f64 - b.g as f64,
+        a.b as f64 - b.b as f64,
+    );
+    ((2.0 + mean / 256.0) * dr * dr + 4.0 * dg * dg + (2.0 + (255.0 - mean) / 256.0) * db * db)
+        .sqrt()
+}
+
+fn reference(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+        .join(format!("{}.png", name))
+}
+
+fn write_png(path: &PathBuf, pixels: &[RGB8]) {
+    if let Err(e) = lodepng::encode_file(path, pixels, SIZE, SIZE, lodepng::ColorType::RGB, 8) {
+        panic!("failed to write {:?}: {:?}", path, e);
+    }
+}
+
+// Differing pixels in red over a faded copy of the reference
+fn diff_image(expected: &[RGB8], actual: &[RGB8]) -> Vec<RGB8> {
+    expected
+        .iter()
+        .zip(actual)
+        .map(|(e, a)| {
+            let d = distance(*e, *a);
+            if d > TOLERANCE {
+                RGB8 { r: 255, g: 0, b: 0 }
+            } else {
+                let grey = ((e.r as u32 + e.g as u32 + e.b as u32) / 12) as u8;
+                RGB8 {
+                    r: grey,
+                    g: grey,
+                    b: grey,
+                }
+            }
+        })
+        .collect()
+}
+
+fn check(scene: &Scene) -> Result<(), String> {
+    let actual = render(scene);
+    let path = reference(scene.name);
+    if env::var_os("GOLDEN_UPDATE").is_some() {
+        write_png(&path, &actual);
+        return Ok(());
+    }
+
+    let expected = match lodepng::decode24_file(&path) {
+        Ok(image) => image,
+        Err(e) => return Err(format!("no reference {:?}: {:?}", path, e)),
+    };
+    if (expected.width, expected.height) != (SIZE, SIZE) {
+        return Err(format!(
+            "{} is {}x{}, expected {}x{}",
+            scene.name, expected.width, expected.height, SIZE, SIZE
+        ));
+    }
+
+    let differing = expected
+        .buffer
+        .iter()
+        .zip(&actual)
+        .filter(|(e, a)| distance(**e, **a) > TOLERANCE)
+        .count();
+    if differing as f64 <= ALLOWED * (SIZE * SIZE) as f64 {
+        return Ok(());
+    }
+
+    let out = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("golden");
+    if let Err(e) = fs::create_dir_all(&out) {
+        panic!("failed to create {:?}: {}", out, e);
+    }
+    let diff = out.join(format!("{}.diff.png", scene.name));
+    write_png(&diff, &diff_image(&expected.buffer, &actual));
+    write_png(&out.join(format!("{}.png", scene.name)), &actual);
+    Err(format!(
+        "{} has {} of {} pixels differing, see {:?}",
+        scene.name,
+        differing,
+        SIZE * SIZE,
+        diff
+    ))
+}
+
+#[test]
+fn renders_match_references() {
+    let failures: Vec<String> = SCENES.iter().filter_map(|s| check(s).err()).collect();
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn distance_ignores_small_changes() {
+    let a = RGB8 {
+        r: 120,
+        g: 60,
+        b: 200,
+    };
+    let b = RGB8 {
+        r: 124,
+        g: 58,
+        b: 203,
+    };
+    assert!(distance(a, a) == 0.0);
+    assert!(distance(a, b) < TOLERANCE);
+    assert!(distance(a, RGB8 { r: 0, g: 0, b: 0 }) > TOLERANCE);
+}
@@ -0,0 +1,45 @@
+extern crate fractal_core;
+extern crate fractal_rs;
+extern crate num;
+
+use fractal_core::MAX_ZOOM;
+use fractal_rs::*;
+use num::complex::Complex64;
+
+// 60 texels across a tile, so a pixel of 1 / 60 is level 0
+fn config(blend_levels: bool) -> RenderConfig {
+    let mut renderer = renderer(Point { x: 4, y: 4 }, 3.0, 50, SampleFilter::Bilinear);
+    renderer.blend_levels = blend_levels;
+    renderer
+}
+
+#[test]
+fn texels_match_pixels() {
+    let renderer = config(true);
+    assert_eq!(renderer.levels(1.0 / 60.0), (0, 0.0));
+    assert_eq!(renderer.levels(1.0 / 120.0), (1, 0.0));
+    assert_eq!(renderer.levels(1.0 / (60.0 * 2f64.powf(2.5))), (2, 0.5));
+    // Zoomed out past the coarsest level
+    assert_eq!(renderer.levels(1.0), (0, 0.0));
+}
+
+#[test]
+fn without_blending_texels_are_never_wider_than_pixels() {
+    let renderer = config(false);
+    assert_eq!(renderer.levels(1.0 / 120.0), (1, 0.0));
+    assert_eq!(renderer.levels(1.0 / (60.0 * 2f64.powf(2.5))), (3, 0.0));
+}
+
+#[test]
+fn levels_stop_at_max_zoom() {
+    let deepest = MAX_ZOOM as usize;
+    assert_eq!(config(true).levels(1e-30), (deepest, 0.0));
+    assert_eq!(config(false).levels(1e-30), (deepest, 0.0));
+
+    // Pixels of about 2^-60 still render
+    let viewport = ViewportConfig {
+        center: ComplexSpace(Complex64 { re: -0.75, im: 0.1 }),
+        zoom: 60.0,
+    };
+    assert_eq!(config(true).render(&viewport).len(), 16);
+}
@@ -0,0 +1,43 @@
+extern crate fractal_rs;
+extern crate num;
+
+use fractal_rs::*;
+use num::complex::Complex64;
+use std::collections::HashMap;
+
+// 8 texels across a tile at zoom 0, so texel i is at re = i / 8
+fn storage(filter: SampleFilter) -> TileStorage {
+    TileStorage {
+        generator: Box::new(GeneratorConfig {
+            size: Point { x: 8, y: 8 },
+            max_iter: 200,
+        }),
+        storage: HashMap::new(),
+        filter,
+    }
+}
+
+fn at(re: f64, im: f64) -> ComplexSpace {
+    ComplexSpace(Complex64 { re, im })
+}
+
+#[test]
+fn nearest_picks_the_closest_texel() {
+    let mut nearest = storage(SampleFilter::Nearest);
+    let mut exact = storage(SampleFilter::Bilinear);
+    let (im, step) = (-0.75, 1.0 / 8.0);
+    for i in 0..8 {
+        let texel = exact.sample(at(-1.0 + i as f64 * step, im), 0);
+        // A quarter texel either side still reads texel i
+        for offset in [-0.25, 0.0, 0.25].iter() {
+            let re = -1.0 + (i as f64 + offset) * step;
+            assert_eq!(
+                nearest.sample(at(re, im), 0),
+                texel,
+                "texel {} {}",
+                i,
+                offset
+            );
+        }
+    }
+}
@@ -0,0 +1,102 @@
+extern crate fractal_core;
+extern crate fractal_rs;
+extern crate num;
+extern crate rgb;
+
+use fractal_core::{render_frame, Colouring, Formula, Scene, Viewport, BOTTOM, RGB};
+use fractal_rs::{scene, ComplexSpace, Point, SampleFilter, ViewportConfig};
+use num::complex::Complex64;
+use rgb::RGB8;
+
+fn banded(width: u32, height: u32, supersample: u32) -> Scene {
+    Scene {
+        formula: Formula::Mandelbrot,
+        center_re: "-0.5".to_string(),
+        center_im: "0".to_string(),
+        zoom: "0".to_string(),
+        max_iter: 100,
+        width,
+        height,
+        supersample,
+        colouring: Colouring::Bands,
+        palette: vec![RGB::rgb(255, 255, 255), RGB::rgb(0, 0, 255)],
+    }
+}
+
+#[test]
+fn banded_scenes_match_the_wasm_renderers() {
+    let (width, height) = (64, 48);
+    let pixels = scene::render(&banded(width, height, 1), SampleFilter::Nearest).unwrap();
+
+    let scene = banded(width, height, 1);
+    let mut expected = vec![BOTTOM; (width * height) as usize];
+    let frame = Viewport::DEFAULT.frame(width, height);
+    render_frame(&mut expected, width, height, &frame, 100, &scene.palette);
+
+    assert_eq!(pixels.len(), expected.len());
+    for (p, e) in pixels.iter().zip(&expected) {
+        assert_eq!((p.r, p.g, p.b), (e.r, e.g, e.b));
+    }
+}
+
+#[test]
+fn supersampling_averages_to_the_output_size() {
+    let (width, height) = (40, 30);
+    let plain = scene::render(&banded(width, height, 1), SampleFilter::Nearest).unwrap();
+    let smooth = scene::render(&banded(width, height, 3), SampleFilter::Nearest).unwrap();
+    assert_eq!(smooth.len(), plain.len());
+
+    // Far outside the set every sample escapes at once, inside none do
+    let corner = (smooth[0].r, smooth[0].g, smooth[0].b);
+    assert_eq!(corner, (plain[0].r, plain[0].g, plain[0].b));
+    let inside = &smooth[(15 * width + 13) as usize];
+    assert_eq!((inside.r, inside.g, inside.b), (0, 0, 0));
+
+    // Blended pixels appear along band edges
+    let blended = smooth
+        .iter()
+        .filter(|p| p.r != 0 && p.r != 255 && p.g == p.r && p.b == 255)
+        .count();
+    assert!(blended > 0);
+}
+
+#[test]
+fn smooth_scenes_render_non_square_sizes() {
+    let mut scene = banded(48, 24, 1);
+    scene.colouring = Colouring::Smooth;
+    scene.max_iter = 50;
+    let (width, height) = (48, 24);
+    let pixels = scene::render(&scene, SampleFilter::Bilinear).unwrap();
+    assert_eq!(pixels.len(), width * height);
+    // The last row is rendered too, not left black
+    assert!(pixels[width * (height - 1)..]
+        .iter()
+        .any(|p| p.r != 0 || p.g != 0 || p.b != 0));
+}
+
+#[test]
+fn saved_view_loads_back() {
+    let viewport = ViewportConfig {
+        center: ComplexSpace(Complex64 {
+            re: -0.743643887037151,
+            im: 0.13182590420533,
+        }),
+        zoom: 7.125,
+    };
+    let size = Point { x: 10, y: 20 };
+    let palette = [RGB8 { r: 1, g: 2, b: 3 }];
+    let saved = scene::from_view(&viewport, &size, 300, &palette).to_string();
+
+    let loaded: Scene = saved.parse().unwrap();
+    assert_eq!(loaded.palette, vec![RGB::rgb(1, 2, 3)]);
+    assert_eq!(
+        (loaded.width, loaded.height, loaded.max_iter),
+        (10, 20, 300)
+    );
+    let view = scene::viewport(&loaded).unwrap();
+    assert_eq!(view.zoom, 7.125);
+    assert_eq!(
+        (view.center.re, view.center.im),
+        (viewport.center.re, viewport.center.im)
+    );
+}
@@ -0,0 +1,84 @@
+extern crate fractal_core;
+extern crate fractal_rs;
+
+use fractal_core::Complex;
+use fractal_rs::simd::SimdGenerator;
+use fractal_rs::*;
+
+fn config(width: usize, max_iter: u64) -> GeneratorConfig {
+    GeneratorConfig {
+        size: Point { x: width, y: 8 },
+        max_iter,
+    }
+}
+
+fn tiles() -> Vec<TileSpace> {
+    let mut tiles = vec![];
+    for zoom in 0..3 {
+        let n = 1 << zoom;
+        for y in -n..n {
+            for x in -2 * n..n {
+                tiles.push(TileSpace {
+                    index: Point { x, y },
+                    zoom,
+                });
+            }
+        }
+    }
+    tiles
+}
+
+fn bits(tile: &Tile) -> Vec<Option<u32>> {
+    tile.data.iter().map(|v| v.map(f32::to_bits)).collect()
+}
+
+fn check(simd: &SimdGenerator) {
+    let scalar = config(simd.config.size.x, simd.config.max_iter);
+    for tile in tiles() {
+        assert_eq!(
+            bits(&simd.generate(&tile)),
+            bits(&scalar.generate(&tile)),
+            "{:?}",
+            tile
+        );
+        assert_eq!(simd.hash(&tile), scalar.hash(&tile));
+    }
+}
+
+#[test]
+fn detected_path_matches_scalar() {
+    // Widths that do and don't split into whole lane groups
+    for &width in &[16, 13, 3] {
+        for &max_iter in &[0, 1, 2, 100] {
+            check(&SimdGenerator::new(config(width, max_iter)));
+        }
+    }
+}
+
+#[test]
+fn fallback_matches_scalar() {
+    for &width in &[16, 13] {
+        check(&SimdGenerator::fallback(config(width, 100)));
+    }
+}
+
+#[test]
+fn escapes_match_core() {
+    let simd = SimdGenerator::new(config(16, 200));
+    for tile in tiles() {
+        let data = simd.generate(&tile);
+        let scale = (1u64 << tile.zoom) as f64;
+        for y in 0..8 {
+            for x in 0..16 {
+                let c = Complex {
+                    re: (tile.index.x as f64 + x as f64 / 16.0) / scale,
+                    im: (tile.index.y as f64 + y as f64 / 8.0) / scale,
+                };
+                assert_eq!(
+                    simd.texel(&data, x, y).is_none(),
+                    mandel_iter(200, c).is_none()
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,131 @@
+// Headless harness for the raw wasm builds
+//
+// Loads a module with wasmi in place of the page, so tests can drive the
+// exported ABI and read its memory back without a browser.
+extern crate wasmi;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use wasmi::{Caller, Engine, Extern, Instance, Linker, Module, Store, WasmParams, WasmResults};
+
+// Floats cross the ABI wrapped, F64::from(x) and to_float()
+pub use wasmi::core::F64;
+
+pub struct Harness {
+    store: Store<Vec<String>>,
+    instance: Instance,
+}
+
+impl Harness {
+    // Instantiates a module, with js_log_msg stubbed to collect debug
+    // messages, see logs
+    pub fn load(path: &Path) -> Result<Harness, String> {
+        let bytes = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let engine = Engine::default();
+        let module = Module::new(&engine, &bytes[..]).map_err(|e| e.to_string())?;
+        let mut store = Store::new(&engine, Vec::new());
+
+        let mut linker = Linker::new(&engine);
+        linker
+            .func_wrap("env", "js_log_msg", log_msg)
+            .map_err(|e| e.to_string())?;
+        let instance = linker
+            .instantiate(&mut store, &module)
+            .and_then(|pre| pre.start(&mut store))
+            .map_err(|e| e.to_string())?;
+        Ok(Harness { store, instance })
+    }
+
+    // Calls an export, failing when it's missing or has another signature
+    pub fn call<P: WasmParams, R: WasmResults>(
+        &mut self,
+        name: &str,
+        params: P,
+    ) -> Result<R, String> {
+        let func = self
+            .instance
+            .get_typed_func::<P, R>(&self.store, name)
+            .map_err(|e| format!("{}: {}", name, e))?;
+        func.call(&mut self.store, params)
+            .map_err(|e| format!("{}: {}", name, e))
+    }
+
+    // len bytes of memory from ptr
+    pub fn read(&self, ptr: u32, len: usize) -> Result<Vec<u8>, String> {
+        let memory = self
+            .instance
+            .get_memory(&self.store, "memory")
+            .ok_or("no memory export")?;
+        let data = memory.data(&self.store);
+        let start = ptr as usize;
+        data.get(start..start + len)
+            .map(|bytes| bytes.to_vec())
+            .ok_or_else(|| format!("{} bytes at {} is out of bounds", len, ptr))
+    }
+
+    // Address of an exported static, like bare-metal-wasm's BUFFER
+    pub fn global(&self, name: &str) -> Result<u32, String> {
+        let global = self
+            .instance
+            .get_global(&self.store, name)
+            .ok_or_else(|| format!("no global {}", name))?;
+        global
+            .get(&self.store)
+            .i32()
+            .map(|ptr| ptr as u32)
+            .ok_or_else(|| format!("{} isn't an i32", name))
+    }
+
+    // Messages sent through js_log_msg so far
+    pub fn logs(&self) -> &[String] {
+        self.store.data()
+    }
+}
+
+// Reads the message out of the module's debug buffer, like the pages do
+fn log_msg(mut caller: Caller<'_, Vec<String>>) {
+    let export = |caller: &Caller<'_, Vec<String>>, name: &str| caller.get_export(name);
+    let (buffer, size, memory) = match (
+        export(&caller, "get_debug_buffer").and_then(Extern::into_func),
+        export(&caller, "get_debug_msg_size").and_then(Extern::into_func),
+        export(&caller, "memory").and_then(Extern::into_memory),
+    ) {
+        (Some(buffer), Some(size), Some(memory)) => (buffer, size, memory),
+        _ => return,
+    };
+    let ptr = buffer
+        .typed::<(), i32>(&caller)
+        .and_then(|f| f.call(&mut caller, ()).map_err(Into::into));
+    let len = size
+        .typed::<(), i32>(&caller)
+        .and_then(|f| f.call(&mut caller, ()).map_err(Into::into));
+    if let (Ok(ptr), Ok(len)) = (ptr, len) {
+        let (ptr, len) = (ptr as usize, len as usize);
+        let msg = match memory.data(&caller).get(ptr..ptr + len) {
+            Some(bytes) => String::from_utf8_lossy(bytes).into_owned(),
+            None => return,
+        };
+        caller.data_mut().push(msg);
+    }
+}
+
+// FNV-1a, stable across platforms and runs so it can be kept as a golden
+// value
+pub fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+// The first of paths, relative to the workspace root, that exists. The
+// WASM_DIR environment variable adds a directory to look in first.
+pub fn locate(name: &str, paths: &[&str]) -> Option<PathBuf> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+    let from_env = env::var_os("WASM_DIR").map(|dir| PathBuf::from(dir).join(name));
+    from_env
+        .into_iter()
+        .chain(paths.iter().map(|path| root.join(path)))
+        .find(|path| path.is_file())
+}
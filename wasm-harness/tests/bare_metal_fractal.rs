@@ -0,0 +1,203 @@
+use fractal_core::{
+    default_palette, iterate_rect, render_frame, Colouring, Rect, Scene, Viewport, BOTTOM, INTERIOR,
+};
+use wasm_harness::{checksum, locate, Harness, F64};
+
+const WIDTH: u32 = 64;
+const HEIGHT: u32 = 48;
+const MAX_ITER: u32 = 100;
+
+// The default view with the default palette, and its raw escape counts
+const GOLDEN_PIXELS: u64 = 0x322e_c59a_e5b4_47d9;
+const GOLDEN_ITERATIONS: u64 = 0xa3e5_73a4_5c5f_8ef4;
+
+// A build of this tree, from cargo or bare-metal-fractal/build.sh. The tests
+// using it are ignored as stable has no wasm32 target, run them with
+// `cargo test -p wasm-harness -- --ignored` after building.
+fn load() -> Harness {
+    let path = locate(
+        "bare_metal_fractal.wasm",
+        &[
+            "target/wasm32-unknown-unknown/release/bare_metal_fractal.wasm",
+            "target/wasm32-unknown-unknown/debug/bare_metal_fractal.wasm",
+            "bare-metal-fractal/www/bare_metal_fractal.wasm",
+        ],
+    )
+    .expect("bare_metal_fractal.wasm isn't built, build it or set WASM_DIR");
+    Harness::load(&path).unwrap()
+}
+
+fn pixels_bytes() -> Vec<u8> {
+    let mut buf = vec![BOTTOM; (WIDTH * HEIGHT) as usize];
+    let frame = Viewport::DEFAULT.frame(WIDTH, HEIGHT);
+    render_frame(
+        &mut buf,
+        WIDTH,
+        HEIGHT,
+        &frame,
+        MAX_ITER as u64,
+        &default_palette(),
+    );
+    buf.iter().flat_map(|p| vec![p.r, p.g, p.b, p.a]).collect()
+}
+
+fn iterations_bytes() -> Vec<u8> {
+    let mut iters = vec![INTERIOR; (WIDTH * HEIGHT) as usize];
+    let frame = Viewport::DEFAULT.frame(WIDTH, HEIGHT);
+    let rect = Rect {
+        x: 0,
+        y: 0,
+        width: WIDTH,
+        height: HEIGHT,
+    };
+    iterate_rect(&mut iters, 0, WIDTH as usize, &frame, rect, MAX_ITER as u64);
+    iters
+        .iter()
+        .flat_map(|i| i.to_le_bytes().to_vec())
+        .collect()
+}
+
+#[test]
+fn native_render_matches_golden() {
+    assert_eq!(checksum(&pixels_bytes()), GOLDEN_PIXELS);
+    assert_eq!(checksum(&iterations_bytes()), GOLDEN_ITERATIONS);
+}
+
+#[test]
+#[ignore = "needs bare_metal_fractal.wasm"]
+fn render_matches_golden() {
+    let mut wasm = load();
+    let size = (4 * WIDTH * HEIGHT) as usize;
+    let tile: i32 = wasm
+        .call("alloc_tile", (WIDTH as i32, HEIGHT as i32))
+        .unwrap();
+    wasm.call::<_, ()>("enable_iterations", tile).unwrap();
+    let view = Viewport::DEFAULT;
+    wasm.call::<_, ()>(
+        "render",
+        (
+            tile,
+            MAX_ITER as i32,
+            F64::from(view.center_re),
+            F64::from(view.center_im),
+            F64::from(view.width),
+        ),
+    )
+    .unwrap();
+
+    let buffer: i32 = wasm.call("get_buffer", tile).unwrap();
+    let pixels = wasm.read(buffer as u32, size).unwrap();
+    assert_eq!(checksum(&pixels), GOLDEN_PIXELS);
+    let iterations: i32 = wasm.call("get_iterations", tile).unwrap();
+    let iterations = wasm.read(iterations as u32, size).unwrap();
+    assert_eq!(checksum(&iterations), GOLDEN_ITERATIONS);
+
+    wasm.call::<_, ()>("free_tile", tile).unwrap();
+    assert!(wasm.logs().is_empty(), "{:?}", wasm.logs());
+}
+
+#[test]
+#[ignore = "needs bare_metal_fractal.wasm"]
+fn progressive_render_matches_golden() {
+    let mut wasm = load();
+    let tile: i32 = wasm
+        .call("alloc_tile", (WIDTH as i32, HEIGHT as i32))
+        .unwrap();
+    wasm.call::<_, ()>("reset_view", ()).unwrap();
+    wasm.call::<_, ()>("start_render", (tile, MAX_ITER as i32, 1))
+        .unwrap();
+    let mut steps = 0;
+    while wasm.call::<_, F64>("step", (tile, 500)).unwrap().to_float() < 1.0 {
+        steps += 1;
+        assert!(steps <= WIDTH * HEIGHT / 500 + 1);
+    }
+
+    let buffer: i32 = wasm.call("get_buffer", tile).unwrap();
+    let pixels = wasm
+        .read(buffer as u32, (4 * WIDTH * HEIGHT) as usize)
+        .unwrap();
+    assert_eq!(checksum(&pixels), GOLDEN_PIXELS);
+    wasm.call::<_, ()>("free_tile", tile).unwrap();
+}
+
+#[test]
+#[ignore = "needs bare_metal_fractal.wasm"]
+fn saved_scene_describes_the_view() {
+    let mut wasm = load();
+    let tile: i32 = wasm
+        .call("alloc_tile", (WIDTH as i32, HEIGHT as i32))
+        .unwrap();
+    wasm.call::<_, ()>("reset_view", ()).unwrap();
+    // As the page works out the zoom
+    let scene_width: F64 = wasm.call("get_scene_width", ()).unwrap();
+    let view_width: F64 = wasm.call("get_view_width", ()).unwrap();
+    let zoom = (scene_width.to_float() / view_width.to_float()).log2();
+    let len: i32 = wasm
+        .call("save_scene", (tile, MAX_ITER as i32, F64::from(zoom)))
+        .unwrap();
+    let text: i32 = wasm.call("get_scene", tile).unwrap();
+    let text = wasm.read(text as u32, len as usize).unwrap();
+
+    let scene: Scene = String::from_utf8(text).unwrap().parse().unwrap();
+    assert_eq!(
+        (scene.center_re.as_str(), scene.center_im.as_str()),
+        ("-0.5", "0")
+    );
+    assert_eq!(scene.zoom, "0");
+    assert_eq!((scene.width, scene.height), (WIDTH, HEIGHT));
+    assert_eq!(scene.max_iter, MAX_ITER as u64);
+    assert_eq!(scene.colouring, Colouring::Bands);
+    assert_eq!(scene.palette, default_palette());
+    wasm.call::<_, ()>("free_tile", tile).unwrap();
+}
+
+#[test]
+#[ignore = "needs bare_metal_fractal.wasm"]
+fn palettes_need_two_stops() {
+    let mut wasm = load();
+    assert_eq!(wasm.call::<_, i32>("alloc_palette", 0).unwrap(), 0);
+    assert_eq!(wasm.call::<_, i32>("alloc_palette", 1).unwrap(), 0);
+    let tile: i32 = wasm
+        .call("alloc_tile", (WIDTH as i32, HEIGHT as i32))
+        .unwrap();
+    assert_eq!(wasm.call::<_, i32>("bind_palette", (tile, 0)).unwrap(), 0);
+    let palette: i32 = wasm.call("alloc_palette", 2).unwrap();
+    assert_ne!(palette, 0);
+    wasm.call::<_, ()>("free_palette", palette).unwrap();
+    wasm.call::<_, ()>("free_tile", tile).unwrap();
+}
+
+#[test]
+#[ignore = "needs bare_metal_fractal.wasm"]
+fn resize_abandons_progressive_render() {
+    let mut wasm = load();
+    let tile: i32 = wasm
+        .call("alloc_tile", (WIDTH as i32 / 2, HEIGHT as i32 / 2))
+        .unwrap();
+    wasm.call::<_, ()>("reset_view", ()).unwrap();
+    wasm.call::<_, ()>("start_render", (tile, MAX_ITER as i32, 1))
+        .unwrap();
+    wasm.call::<_, i32>("resize_tile", (tile, WIDTH as i32, HEIGHT as i32))
+        .unwrap();
+
+    // Nothing left to step, and nothing counts as rendered at the new size
+    let progress = wasm.call::<_, F64>("step", (tile, 500)).unwrap();
+    assert_eq!(progress.to_float(), 1.0);
+    let panned: i32 = wasm
+        .call(
+            "pan_render",
+            (tile, F64::from(0.0), F64::from(0.0), MAX_ITER as i32),
+        )
+        .unwrap();
+    assert_eq!(panned, 0);
+
+    wasm.call::<_, ()>("start_render", (tile, MAX_ITER as i32, 1))
+        .unwrap();
+    while wasm.call::<_, F64>("step", (tile, 500)).unwrap().to_float() < 1.0 {}
+    let buffer: i32 = wasm.call("get_buffer", tile).unwrap();
+    let pixels = wasm
+        .read(buffer as u32, (4 * WIDTH * HEIGHT) as usize)
+        .unwrap();
+    assert_eq!(checksum(&pixels), GOLDEN_PIXELS);
+    wasm.call::<_, ()>("free_tile", tile).unwrap();
+}
@@ -0,0 +1,28 @@
+use wasm_harness::{checksum, locate, Harness};
+
+const WIDTH: usize = 600;
+const HEIGHT: usize = 600;
+
+// go's first two frames of BUFFER
+const GOLDEN: [u64; 2] = [0x7be7_c355_bde2_1065, 0xb4b2_f67c_340b_8d05];
+
+#[test]
+fn go_matches_golden_frames() {
+    let path = locate(
+        "bare_metal_wasm.wasm",
+        &[
+            "target/wasm32-unknown-unknown/release/bare_metal_wasm.wasm",
+            "bare-metal-wasm/www/bare_metal_wasm.wasm",
+        ],
+    )
+    .expect("bare_metal_wasm.wasm is missing, it's checked in at bare-metal-wasm/www");
+    let mut wasm = Harness::load(&path).unwrap();
+    assert_eq!(wasm.call::<(), u32>("the_answer", ()).unwrap(), 42);
+
+    let buffer = wasm.global("BUFFER").unwrap();
+    for golden in GOLDEN.iter() {
+        wasm.call::<(), ()>("go", ()).unwrap();
+        let frame = wasm.read(buffer, 4 * WIDTH * HEIGHT).unwrap();
+        assert_eq!(checksum(&frame), *golden);
+    }
+}